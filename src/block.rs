@@ -2,9 +2,14 @@ use std::{fmt::Display, path::PathBuf};
 
 use smartstring::{LazyCompact, SmartString};
 
-use crate::{color::Color, dto::BlockDto, painting::Painting};
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use crate::{
+    color::Color,
+    dto::BlockDto,
+    error::{Error, Result},
+    painting::Painting,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Point {
     pub x: u32,
     pub y: u32,
@@ -16,7 +21,7 @@ impl Point {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Rect {
     /// inclusive lower bound
     pub bottom_left: Point,
@@ -84,9 +89,9 @@ impl Rect {
 
     pub fn strictly_contains(&self, x: u32, y: u32) -> bool {
         x > self.bottom_left.x
-            && x + 1 < self.top_right.x
+            && x < self.top_right.x
             && y > self.bottom_left.y
-            && y + 1 < self.top_right.y
+            && y < self.top_right.y
     }
 
     pub fn vertical_cut(&self, x: u32) -> (Self, Self) {
@@ -260,6 +265,30 @@ impl Block {
         self.r.width() * self.r.height()
     }
 
+    /// The same contents with the bottom left corner at `bottom_left`, as a swap
+    /// leaves them
+    pub fn moved_to(&self, bottom_left: Point) -> Self {
+        let shift = |r: &Rect| {
+            Rect::from_coords([
+                r.x() - self.r.x() + bottom_left.x,
+                r.y() - self.r.y() + bottom_left.y,
+                r.top_right.x - self.r.x() + bottom_left.x,
+                r.top_right.y - self.r.y() + bottom_left.y,
+            ])
+        };
+        let data = match &self.data {
+            BlockData::Simple(c) => BlockData::Simple(*c),
+            BlockData::Complex(bs) => {
+                BlockData::Complex(bs.iter().map(|b| SubBlock::new(shift(&b.r), b.c)).collect())
+            }
+        };
+        Block {
+            id: self.id.clone(),
+            r: shift(&self.r),
+            data,
+        }
+    }
+
     pub fn take_children(self) -> Vec<SubBlock> {
         match self.data {
             BlockData::Simple(c) => vec![SubBlock::new(self.r, c)],
@@ -274,7 +303,7 @@ impl Block {
         }
     }
 
-    pub fn from_dto(dto: &BlockDto, source_png_path: Option<String>) -> Self {
+    pub fn from_dto(dto: &BlockDto, source_png_path: Option<String>) -> Result<Self> {
         let BlockDto {
             block_id,
             bottom_left: [bl_x, bl_y],
@@ -284,14 +313,18 @@ impl Block {
         } = dto;
 
         match (color, png_bottom_left) {
-            (Some([r, g, b, a]), None) => Block::new_simple(
+            (Some([r, g, b, a]), None) => Ok(Block::new_simple(
                 BlockId::new(block_id.clone()),
                 Rect::from_coords([*bl_x, *bl_y, *tr_x, *tr_y]),
                 Color::new(*r, *g, *b, *a),
-            ),
+            )),
             (None, Some([png_bl_x, png_bl_y])) => {
-                let path = PathBuf::from(source_png_path.expect("Missing source_png_path"));
-                let painting = Painting::load(&path);
+                let path = PathBuf::from(source_png_path.ok_or_else(|| {
+                    Error::InvalidProblem(format!(
+                        "block {block_id} refers to a source PNG, but the canvas has none"
+                    ))
+                })?);
+                let painting = Painting::load(&path)?;
                 let mut blocks = vec![];
 
                 for x in *png_bl_x..*tr_x {
@@ -304,9 +337,15 @@ impl Block {
                 }
 
                 let rect = Rect::from_coords([*bl_x, *bl_y, *tr_x, *tr_y]);
-                Block::new_complex(BlockId::new(block_id.clone()), rect, blocks)
+                Ok(Block::new_complex(
+                    BlockId::new(block_id.clone()),
+                    rect,
+                    blocks,
+                ))
             }
-            _ => panic!("Invalid initial JSON"),
+            _ => Err(Error::InvalidProblem(format!(
+                "block {block_id} must have either a color or a source PNG point"
+            ))),
         }
     }
 }
@@ -4,9 +4,10 @@ use std::{
 };
 
 use crate::{
-    block::{Block, BlockData, BlockId, Point, Rect},
+    block::{Block, BlockData, BlockId, Point, Rect, SubBlock},
     color::Color,
     dto::CanvasDto,
+    error::{Error, Result, ResultExt},
     moves::{Cost, MoveType},
     painting::Painting,
 };
@@ -20,15 +21,19 @@ pub struct Canvas {
     blocks: HashMap<BlockId, Block>,
     roots_count: u32,
     pub generation: u32,
+    /// How many target pixels every pixel stands for along each axis, see `downscale`
+    scale: u32,
 }
 
-impl From<CanvasDto> for Canvas {
-    fn from(dto: CanvasDto) -> Self {
+impl TryFrom<CanvasDto> for Canvas {
+    type Error = Error;
+
+    fn try_from(dto: CanvasDto) -> Result<Self> {
         let blocks: Vec<Block> = dto
             .blocks
             .iter()
             .map(|bdto| Block::from_dto(bdto, dto.source_png.clone()))
-            .collect();
+            .collect::<Result<_>>()?;
 
         let max_root: Option<u32> = blocks
             .iter()
@@ -45,14 +50,14 @@ impl From<CanvasDto> for Canvas {
 
         let initial_root_count = max_root.map_or(0, |max_root| max_root + 1);
 
-        Canvas::from_blocks(
+        Ok(Canvas::from_blocks(
             dto.width,
             dto.height,
             initial_root_count,
             0,
             blocks.into_iter(),
             dto.source_png.is_some(),
-        )
+        ))
     }
 }
 
@@ -109,6 +114,7 @@ impl Canvas {
             blocks: blocks_map,
             roots_count,
             generation,
+            scale: 1,
         }
     }
 
@@ -171,7 +177,56 @@ impl Canvas {
     }
 
     pub fn compute_cost(&self, mov: MoveType, block_area: u32) -> Cost {
-        Cost((self.base_cost(mov) * (self.area as f64 / block_area as f64)).round() as u64)
+        let scale = (self.scale * self.scale) as f64;
+        Cost((self.base_cost(mov) * (self.area as f64 / block_area as f64) / scale).round() as u64)
+    }
+
+    /// The canvas at `1 / factor` of the resolution, with the block contents averaged.
+    /// Its similarity is computed over `factor²` times fewer pixels, so the move
+    /// costs get divided by as much to keep the same balance between the two.
+    pub fn downscale(&self, factor: u32) -> Result<Canvas> {
+        let scaled = |c: u32| {
+            if c.is_multiple_of(factor) {
+                Ok(c / factor)
+            } else {
+                Err(Error::Solver(format!(
+                    "can't downscale the canvas {factor}x, {c} isn't a multiple of it"
+                )))
+            }
+        };
+        let rendered = self.render().downscale(factor);
+        let mut blocks = vec![];
+        for b in self.blocks.values() {
+            let r = Rect::from_coords([
+                scaled(b.r.x())?,
+                scaled(b.r.y())?,
+                scaled(b.r.top_right.x)?,
+                scaled(b.r.top_right.y)?,
+            ]);
+            blocks.push(match b.data {
+                BlockData::Simple(c) => Block::new_simple(b.id.clone(), r, c),
+                BlockData::Complex(_) => {
+                    let mut sub_blocks = vec![];
+                    for x in r.x()..r.top_right.x {
+                        for y in r.y()..r.top_right.y {
+                            let pixel = Rect::from_coords([x, y, x + 1, y + 1]);
+                            sub_blocks.push(SubBlock::new(pixel, rendered.get_color(x, y)));
+                        }
+                    }
+                    Block::new_complex(b.id.clone(), r, sub_blocks)
+                }
+            });
+        }
+        let mut canvas = Canvas::from_blocks(
+            scaled(self.width)?,
+            scaled(self.height)?,
+            self.roots_count,
+            self.generation,
+            blocks.into_iter(),
+            self.v2,
+        );
+        canvas.scale = self.scale * factor;
+        Ok(canvas)
     }
 
     pub fn render(&self) -> Painting {
@@ -203,20 +258,18 @@ impl Canvas {
         }
     }
 
-    fn load_canvas(path: &Path) -> std::io::Result<Self> {
+    fn load_canvas(path: &Path) -> Result<Self> {
         let txt = std::fs::read_to_string(path)?;
         let dto: CanvasDto = serde_json::from_str(&txt)?;
-        Ok(dto.into())
+        dto.try_into()
     }
 
-    pub fn try_create(
-        initial_config_path: PathBuf,
-        painting: &Painting,
-    ) -> Result<Canvas, std::io::Error> {
+    pub fn try_create(initial_config_path: PathBuf, painting: &Painting) -> Result<Canvas> {
         let canvas = match initial_config_path.try_exists() {
-            Ok(true) => Canvas::load_canvas(&initial_config_path)?,
+            Ok(true) => Canvas::load_canvas(&initial_config_path)
+                .with_context(|| format!("couldn't load {}", initial_config_path.display()))?,
             Ok(false) => Canvas::new(painting.width(), painting.height()),
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         };
 
         Ok(canvas)
@@ -1,78 +1,104 @@
 use std::{
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use crate::{
-    gui::gui_main,
+#[cfg(not(feature = "gui"))]
+use crate::cmd::render::render;
+#[cfg(feature = "gui")]
+use icfpc2022::gui::gui_main;
+use icfpc2022::{
+    dto::SolvedSolutionDto,
+    error::{Error, Result},
     moves::Move,
     solvers::Problem,
     solvers::{create_solver, Solution, Solver},
 };
 use rayon::prelude::*;
 
+fn run_solver(solver: &dyn Solver, base_solution_dir: &Path, problem: &Problem) -> Result<()> {
+    let mut canvas = problem.initial_canvas.clone();
+    let solution = solver.solve(&mut canvas, &problem.reference_painting)?;
+    record_solution(solver.name(), &solution, base_solution_dir, problem)?;
+    Ok(())
+}
+
+/// Saves the solution as the current one of the solver, and as the best one if it wins
+pub(crate) fn record_solution(
+    full_solver_name: &str,
+    solution: &Solution,
+    base_solution_dir: &Path,
+    problem: &Problem,
+) -> Result<SolvedSolutionDto> {
+    let cur_solver_dir = &base_solution_dir.join("current").join(full_solver_name);
+    let best_dir = &base_solution_dir.join("best");
+    std::fs::create_dir_all(cur_solver_dir)?;
+
+    // write the solution
+    let solution_meta = solution.save(full_solver_name.into(), problem, cur_solver_dir)?;
+
+    // compare with the best solution
+    let best_sol = match Solution::load(best_dir, problem) {
+        Ok(sol) => Some(sol),
+        Err(ref e) if e.is_not_found() => None,
+        Err(e) => return Err(e),
+    };
+
+    let new_best_sol = match &best_sol {
+        Some((_, best_sol)) if solution_meta.total_score < best_sol.total_score => true,
+        None => true,
+        _ => false,
+    };
+
+    if new_best_sol {
+        solution.save(full_solver_name.into(), problem, best_dir)?;
+    }
+
+    print!(
+        "{:15}{}: {} ",
+        format!("[problem {}]", problem.id),
+        full_solver_name,
+        solution_meta.summarize()
+    );
+
+    match (&best_sol, &new_best_sol) {
+        // new best
+        (Some((_, best_sol)), true) => {
+            let improvement = best_sol.total_score - solution_meta.total_score;
+            println!(
+                "!!! WE ARE WINNING SON !!!, improvement of {}! previous best: {}",
+                improvement,
+                best_sol.summarize()
+            );
+        }
+        // nothing special, no new best
+        (Some((_, best_sol)), false) => {
+            println!("lower than best: {}", best_sol.summarize());
+        }
+        // first solution ever
+        (None, _) => {
+            println!("!!! FIRST BLOOD !!!");
+        }
+    }
+    Ok(solution_meta)
+}
+
 fn solve_problem(
     solvers: &Vec<Box<dyn Solver>>,
     base_solution_dir: &PathBuf,
     problem_path: &PathBuf,
-) -> std::io::Result<()> {
+) -> Result<()> {
     let problem = Problem::load(problem_path)?;
 
+    // a failing solver shouldn't prevent the others from running
     for solver in solvers {
-        let full_solver_name = solver.name();
-        let mut canvas = problem.initial_canvas.clone();
-        let cur_solver_dir = &base_solution_dir.join("current").join(full_solver_name);
-        let best_dir = &base_solution_dir.join("best");
-        std::fs::create_dir_all(cur_solver_dir)?;
-
-        // solve
-        let solution = solver.solve(&mut canvas, &problem.reference_painting);
-
-        // write the solution
-        let solution_meta = solution.save(full_solver_name.into(), &problem, cur_solver_dir)?;
-
-        // compare with the best solution
-        let best_sol = match Solution::load(best_dir, &problem) {
-            Ok(sol) => Some(sol),
-            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => None,
-            Err(e) => return Err(e),
-        };
-
-        let new_best_sol = match &best_sol {
-            Some((_, best_sol)) if solution_meta.total_score < best_sol.total_score => true,
-            None => true,
-            _ => false,
-        };
-
-        if new_best_sol {
-            solution.save(full_solver_name.into(), &problem, best_dir)?;
-        }
-
-        print!(
-            "{:15}{}: {} ",
-            format!("[problem {}]", problem.id),
-            solver.name(),
-            solution_meta.summarize()
-        );
-
-        match (&best_sol, &new_best_sol) {
-            // new best
-            (Some((_, best_sol)), true) => {
-                let improvement = best_sol.total_score - solution_meta.total_score;
-                println!(
-                    "!!! WE ARE WINNING SON !!!, improvement of {}! previous best: {}",
-                    improvement,
-                    best_sol.summarize()
-                );
-            }
-            // nothing special, no new best
-            (Some((_, best_sol)), false) => {
-                println!("lower than best: {}", best_sol.summarize());
-            }
-            // first solution ever
-            (None, _) => {
-                println!("!!! FIRST BLOOD !!!");
-            }
+        if let Err(e) = run_solver(solver.as_ref(), base_solution_dir, &problem) {
+            eprintln!(
+                "{:15}{}: FAILED: {}",
+                format!("[problem {}]", problem.id),
+                solver.name(),
+                e
+            );
         }
     }
     Ok(())
@@ -82,30 +108,60 @@ fn solve(
     input_moves: Option<Vec<Move>>,
     solvers: &[String],
     problem_paths: &[PathBuf],
-) -> std::io::Result<()> {
+) -> Result<()> {
     let base_solution_dir = PathBuf::from("./solutions/");
 
     let solvers: Vec<_> = solvers
         .iter()
-        .map(|solver_name| create_solver(input_moves.clone(), solver_name))
+        .filter_map(
+            |solver_name| match create_solver(input_moves.clone(), solver_name) {
+                Ok(solver) => Some(solver),
+                Err(e) => {
+                    eprintln!("skipping solver `{solver_name}`: {e}");
+                    None
+                }
+            },
+        )
         .collect();
 
-    problem_paths
+    let failed = problem_paths
         .par_iter()
-        .map(|problem_path| solve_problem(&solvers, &base_solution_dir, problem_path))
-        .collect::<std::io::Result<()>>()
+        .filter(
+            |problem_path| match solve_problem(&solvers, &base_solution_dir, problem_path) {
+                Ok(()) => false,
+                Err(e) => {
+                    eprintln!("{}: FAILED: {}", problem_path.display(), e);
+                    true
+                }
+            },
+        )
+        .count();
+
+    if failed > 0 {
+        return Err(Error::Solver(format!(
+            "{} of {} problems failed",
+            failed,
+            problem_paths.len()
+        )));
+    }
+    Ok(())
 }
 
 pub fn default_command(
     input_moves: Option<Vec<Move>>,
     problem_paths: &[PathBuf],
     solvers: Option<Vec<String>>,
-) -> Result<(), std::io::Error> {
+) -> Result<()> {
     match (problem_paths, solvers) {
-        ([problem_path], None) => {
-            gui_main(input_moves, &std::path::PathBuf::from(problem_path));
-            Ok(())
-        }
+        #[cfg(feature = "gui")]
+        ([problem_path], None) => gui_main(input_moves, &std::path::PathBuf::from(problem_path)),
+        // without the GUI, show the solution the same way the `render` command does
+        #[cfg(not(feature = "gui"))]
+        ([problem_path], None) => render(
+            input_moves,
+            std::slice::from_ref(problem_path),
+            std::path::Path::new("./render"),
+        ),
         (paths, Some(solvers)) => solve(input_moves, &solvers, paths),
         (_, None) => panic!("No problem paths and solvers provided"),
     }
@@ -1,7 +1,15 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
+use self::replay::ReplayFormat;
+
 pub mod default;
+pub mod portfolio;
+pub mod render;
+pub mod replay;
 pub mod stats;
+pub mod tune;
 
 #[derive(Parser, Debug)]
 #[clap()]
@@ -21,4 +29,44 @@ pub struct Args {
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     Stats,
+    /// Write the final painting, a block overlay and a diff heatmap for each problem
+    Render {
+        #[clap(short, long, default_value = "./render")]
+        output_dir: PathBuf,
+    },
+    /// Run all solvers and chains on every problem in parallel, keeping the best results
+    Portfolio {
+        /// Wall-clock budget of the whole run, in seconds
+        #[clap(short, long, default_value_t = 600)]
+        budget: u64,
+        /// How many solvers run at the same time, all the cores by default
+        #[clap(short, long)]
+        jobs: Option<usize>,
+    },
+    /// Replay a solution move by move, writing PNG frames or an animated PNG or GIF
+    Replay {
+        #[clap(short, long, default_value = "./replay")]
+        output_dir: PathBuf,
+        /// Emit a frame every N moves
+        #[clap(short = 'n', long, default_value_t = 1)]
+        every: usize,
+        #[clap(short, long, value_enum, default_value_t = ReplayFormat::Frames)]
+        format: ReplayFormat,
+        /// Delay between the frames of the animations, in milliseconds
+        #[clap(long, default_value_t = 200)]
+        delay_ms: u32,
+    },
+    /// Search the parameters of the given solvers on the given problems
+    Tune {
+        /// Values to try as `key=v1,v2,...`, replacing the defaults of the key
+        #[clap(long = "param")]
+        params: Vec<String>,
+        /// Try this many random configurations instead of the whole grid
+        #[clap(short, long)]
+        random: Option<usize>,
+        #[clap(long)]
+        seed: Option<u64>,
+        #[clap(short, long, default_value = "./tune")]
+        output_dir: PathBuf,
+    },
 }
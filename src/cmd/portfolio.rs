@@ -0,0 +1,216 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use icfpc2022::{
+    dto::SolvedSolutionDto,
+    error::Result,
+    moves::Move,
+    solvers::{create_solver, Problem, Solver, CHAINS, SOLVERS},
+};
+
+use crate::cmd::default::record_solution;
+
+#[derive(Debug, Clone)]
+pub struct PortfolioOptions {
+    /// Wall-clock budget of the whole run, runs still going afterwards are abandoned
+    pub budget: Duration,
+    /// How many solvers run at the same time
+    pub jobs: usize,
+}
+
+/// Runs every solver (all of `SOLVERS` and `CHAINS` unless some are given) on every
+/// problem, most promising runs first, and records the results as they come in
+pub fn portfolio(
+    input_moves: Option<Vec<Move>>,
+    problem_paths: &[PathBuf],
+    solvers: &[String],
+    options: &PortfolioOptions,
+) -> Result<()> {
+    let deadline = Instant::now() + options.budget;
+    let base_solution_dir = PathBuf::from("./solutions/");
+
+    let names: Vec<String> = if solvers.is_empty() {
+        SOLVERS
+            .iter()
+            .chain(CHAINS)
+            .map(|s| s.to_string())
+            .collect()
+    } else {
+        solvers.to_vec()
+    };
+    let solvers: Vec<Box<dyn Solver>> = names
+        .iter()
+        .filter_map(
+            |solver_name| match create_solver(input_moves.clone(), solver_name) {
+                Ok(solver) => Some(solver),
+                Err(e) => {
+                    eprintln!("skipping solver `{solver_name}`: {e}");
+                    None
+                }
+            },
+        )
+        .collect();
+    let problems = problem_paths
+        .iter()
+        .map(Problem::load)
+        .collect::<Result<Vec<_>>>()?;
+
+    // the most promising solver of every problem goes first, then the second ones, etc.
+    let wins = count_wins(&base_solution_dir.join("best"))?;
+    let mut queue = vec![];
+    for (p, problem) in problems.iter().enumerate() {
+        let ranked = rank_solvers(&solvers, &wins, &base_solution_dir, problem);
+        queue.extend(ranked.into_iter().enumerate().map(|(rank, s)| (rank, p, s)));
+    }
+    queue.sort();
+    let runs = queue.len();
+
+    let queue = Arc::new(Mutex::new(VecDeque::from(queue)));
+    let problems = Arc::new(problems);
+    let solvers = Arc::new(solvers);
+    // set once the results stop being collected, so that no worker starts another run
+    let stopped = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..options.jobs.max(1) {
+        let (queue, problems, solvers, stopped, tx) = (
+            queue.clone(),
+            problems.clone(),
+            solvers.clone(),
+            stopped.clone(),
+            tx.clone(),
+        );
+        // not scoped: the solvers can't be interrupted, so a run still going past the
+        // deadline is left behind when the command returns, and its result dropped
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) && Instant::now() < deadline {
+                let Some((_, p, s)) = queue.lock().unwrap().pop_front() else {
+                    break;
+                };
+                let problem = &problems[p];
+                let mut canvas = problem.initial_canvas.clone();
+                let solution = solvers[s].solve(&mut canvas, &problem.reference_painting);
+                if stopped.load(Ordering::Relaxed) || tx.send((p, s, solution)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut best: Vec<Option<SolvedSolutionDto>> = vec![None; problems.len()];
+    let mut finished = 0;
+    loop {
+        let (p, s, solution) =
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(result) => result,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            };
+        finished += 1;
+        let (problem, solver) = (&problems[p], &solvers[s]);
+        let recorded = solution.and_then(|solution| {
+            record_solution(solver.name(), &solution, &base_solution_dir, problem)
+        });
+        match recorded {
+            Ok(meta) => {
+                if best[p]
+                    .as_ref()
+                    .is_none_or(|b| meta.total_score < b.total_score)
+                {
+                    best[p] = Some(meta);
+                }
+            }
+            Err(e) => eprintln!(
+                "{:15}{}: FAILED: {}",
+                format!("[problem {}]", problem.id),
+                solver.name(),
+                e
+            ),
+        }
+    }
+
+    stopped.store(true, Ordering::Relaxed);
+    if finished < runs {
+        eprintln!(
+            "out of time: {} of {} runs didn't finish",
+            runs - finished,
+            runs
+        );
+    }
+    let mut total = 0;
+    for (problem, best) in problems.iter().zip(&best) {
+        match best {
+            Some(meta) => {
+                total += meta.total_score;
+                println!(
+                    "problem {}: {} {}",
+                    problem.id,
+                    meta.solver_name,
+                    meta.summarize()
+                );
+            }
+            None => println!("problem {}: no solution", problem.id),
+        }
+    }
+    println!("Sum of this run: {total}");
+    Ok(())
+}
+
+/// How many problems every solver currently holds the best solution of
+fn count_wins(best_dir: &Path) -> Result<HashMap<String, usize>> {
+    let mut wins = HashMap::new();
+    let entries = match std::fs::read_dir(best_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(wins),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let is_meta = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.ends_with("_meta.json"));
+        if let Some(meta) = is_meta.then(|| load_meta(&path)).flatten() {
+            *wins.entry(meta.solver_name).or_default() += 1;
+        }
+    }
+    Ok(wins)
+}
+
+/// The solvers with a stored solution of the problem come first, best score first,
+/// then the others, the ones winning the most problems first
+fn rank_solvers(
+    solvers: &[Box<dyn Solver>],
+    wins: &HashMap<String, usize>,
+    base_solution_dir: &Path,
+    problem: &Problem,
+) -> Vec<usize> {
+    let mut ranked: Vec<(u64, Reverse<usize>, usize)> = solvers
+        .iter()
+        .enumerate()
+        .map(|(s, solver)| {
+            let meta_path = base_solution_dir
+                .join("current")
+                .join(solver.name())
+                .join(format!("{}_meta.json", problem.id));
+            let score = load_meta(&meta_path).map_or(u64::MAX, |m| m.total_score);
+            let wins = wins.get(solver.name()).copied().unwrap_or_default();
+            (score, Reverse(wins), s)
+        })
+        .collect();
+    ranked.sort();
+    ranked.into_iter().map(|(.., s)| s).collect()
+}
+
+fn load_meta(path: &Path) -> Option<SolvedSolutionDto> {
+    let json = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&json).ok()
+}
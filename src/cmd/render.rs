@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use icfpc2022::{
+    canvas::Canvas,
+    error::{Result, ResultExt},
+    moves::{AppliedMove, Cost, Move},
+    overlay::{abs_diff_gradient, abs_diff_heatmap, blocks_overlay},
+    parser::parse_moves_from_file,
+    solvers::Problem,
+};
+
+/// Without explicit moves, use the best known solution for the problem
+pub fn load_problem_moves(input_moves: &Option<Vec<Move>>, problem: &Problem) -> Result<Vec<Move>> {
+    match input_moves {
+        Some(moves) => Ok(moves.clone()),
+        None => parse_moves_from_file(
+            PathBuf::from("./solutions/best")
+                .join(&problem.id)
+                .with_extension("txt"),
+        ),
+    }
+}
+
+pub fn apply_problem_move(
+    mov: Move,
+    canvas: &mut Canvas,
+    problem: &Problem,
+) -> Result<AppliedMove> {
+    mov.apply(canvas)
+        .with_context(|| format!("problem {}", problem.id))
+}
+
+fn render_problem(
+    input_moves: &Option<Vec<Move>>,
+    output_dir: &Path,
+    problem_path: &PathBuf,
+) -> Result<()> {
+    let problem = Problem::load(problem_path)?;
+    let moves = load_problem_moves(input_moves, &problem)?;
+
+    let mut canvas = problem.initial_canvas.clone();
+    let mut cost = Cost(0);
+    for mov in moves {
+        cost += apply_problem_move(mov, &mut canvas, &problem)?.cost;
+    }
+
+    let base = output_dir.join(&problem.id);
+    canvas.render().write_to_file(&base.with_extension("png"))?;
+    blocks_overlay(&canvas, &[])
+        .write_to_file(&output_dir.join(format!("{}_blocks.png", problem.id)))?;
+    abs_diff_heatmap(&canvas, &problem.reference_painting, &abs_diff_gradient())
+        .write_to_file(&output_dir.join(format!("{}_diff.png", problem.id)))?;
+
+    let score = problem.reference_painting.calculate_score_canvas(&canvas);
+    println!(
+        "{:15}rendered: total {} = (delta {} + moves {})",
+        format!("[problem {}]", problem.id),
+        (score + cost).0,
+        score.0,
+        cost.0
+    );
+    Ok(())
+}
+
+pub fn render(
+    input_moves: Option<Vec<Move>>,
+    problem_paths: &[PathBuf],
+    output_dir: &Path,
+) -> Result<()> {
+    std::fs::create_dir_all(output_dir)?;
+
+    problem_paths
+        .par_iter()
+        .map(|problem_path| render_problem(&input_moves, output_dir, problem_path))
+        .collect::<Result<()>>()
+}
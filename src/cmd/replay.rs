@@ -0,0 +1,179 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame,
+};
+use rayon::prelude::*;
+
+use icfpc2022::{
+    block::BlockId,
+    error::Result,
+    moves::{AppliedMove, Cost, Move},
+    overlay::blocks_overlay,
+    painting::Painting,
+    program::to_isl,
+    solvers::Problem,
+};
+
+use crate::cmd::render::{apply_problem_move, load_problem_moves};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReplayFormat {
+    /// Numbered PNG frames in a directory per problem
+    Frames,
+    /// A single animated PNG
+    Apng,
+    /// A single animated GIF
+    Gif,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    /// Emit a frame every `every` moves (the last move always gets a frame)
+    pub every: usize,
+    pub format: ReplayFormat,
+    /// Delay between the frames of the animations
+    pub delay_ms: u32,
+}
+
+enum FrameSink {
+    Pngs(PathBuf),
+    Apng(png::Writer<BufWriter<File>>),
+    Gif(GifEncoder<File>),
+}
+
+impl FrameSink {
+    /// APNG lists its frame count up front, hence `frames`
+    fn new(
+        options: &ReplayOptions,
+        output_dir: &Path,
+        problem: &Problem,
+        frames: usize,
+    ) -> Result<Self> {
+        let animation_path = |extension| output_dir.join(&problem.id).with_extension(extension);
+        match options.format {
+            ReplayFormat::Frames => {
+                let frames_dir = output_dir.join(&problem.id);
+                std::fs::create_dir_all(&frames_dir)?;
+                Ok(FrameSink::Pngs(frames_dir))
+            }
+            ReplayFormat::Apng => {
+                let file = BufWriter::new(File::create(animation_path("png"))?);
+                let painting = &problem.reference_painting;
+                let mut encoder = png::Encoder::new(file, painting.width(), painting.height());
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(frames as u32, 0)?;
+                // the delay is a fraction of a second with 16-bit terms
+                let delay_ms = options.delay_ms.min(u16::MAX as u32) as u16;
+                encoder.set_frame_delay(delay_ms, 1000)?;
+                Ok(FrameSink::Apng(encoder.write_header()?))
+            }
+            ReplayFormat::Gif => {
+                let file = File::create(animation_path("gif"))?;
+                let mut encoder = GifEncoder::new(file);
+                encoder.set_repeat(Repeat::Infinite)?;
+                Ok(FrameSink::Gif(encoder))
+            }
+        }
+    }
+
+    fn push(&mut self, index: usize, frame: &Painting, delay_ms: u32) -> Result<()> {
+        match self {
+            FrameSink::Pngs(dir) => frame.write_to_file(&dir.join(format!("{index:04}.png"))),
+            FrameSink::Apng(writer) => Ok(writer.write_image_data(&frame.to_image().into_raw())?),
+            FrameSink::Gif(encoder) => Ok(encoder.encode_frame(Frame::from_parts(
+                frame.to_image(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(delay_ms, 1),
+            ))?),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            FrameSink::Apng(writer) => Ok(writer.finish()?),
+            FrameSink::Pngs(_) | FrameSink::Gif(_) => Ok(()),
+        }
+    }
+}
+
+/// The blocks a move acted upon, as they exist after the move
+fn active_blocks(am: &AppliedMove) -> Vec<BlockId> {
+    match &am.mov {
+        Move::Color(id, _) => vec![id.clone()],
+        Move::Swap(a, b) => vec![a.clone(), b.clone()],
+        Move::LineCut(..) | Move::PointCut(..) | Move::Merge(..) => am.created_blocks(),
+    }
+}
+
+fn replay_problem(
+    input_moves: &Option<Vec<Move>>,
+    options: &ReplayOptions,
+    output_dir: &Path,
+    problem_path: &PathBuf,
+) -> Result<()> {
+    let problem = Problem::load(problem_path)?;
+    let moves = load_problem_moves(input_moves, &problem)?;
+    let moves_count = moves.len();
+    let every = options.every.max(1);
+    let tag = format!("[problem {}]", problem.id);
+
+    // the initial canvas, then one frame every `every` moves and one after the last
+    let frames = 1 + moves_count.div_ceil(every);
+    let mut sink = FrameSink::new(options, output_dir, &problem, frames)?;
+    let mut canvas = problem.initial_canvas.clone();
+    let mut cost = Cost(0);
+
+    let mut frame_index = 0;
+    sink.push(frame_index, &blocks_overlay(&canvas, &[]), options.delay_ms)?;
+
+    for (i, mov) in moves.into_iter().enumerate() {
+        let am = apply_problem_move(mov, &mut canvas, &problem)?;
+        cost += am.cost;
+
+        if (i + 1) % every != 0 && i + 1 != moves_count {
+            continue;
+        }
+
+        frame_index += 1;
+        sink.push(
+            frame_index,
+            &blocks_overlay(&canvas, &active_blocks(&am)),
+            options.delay_ms,
+        )?;
+
+        let score = problem.reference_painting.calculate_score_canvas(&canvas);
+        println!(
+            "{:15}frame {:4} move {:4}: {:30} total {} = (delta {} + moves {})",
+            tag,
+            frame_index,
+            i + 1,
+            to_isl(&am.mov),
+            (score + cost).0,
+            score.0,
+            cost.0
+        );
+    }
+    sink.finish()
+}
+
+pub fn replay(
+    input_moves: Option<Vec<Move>>,
+    problem_paths: &[PathBuf],
+    output_dir: &Path,
+    options: &ReplayOptions,
+) -> Result<()> {
+    std::fs::create_dir_all(output_dir)?;
+
+    problem_paths
+        .par_iter()
+        .map(|problem_path| replay_problem(&input_moves, options, output_dir, problem_path))
+        .collect::<Result<()>>()
+}
@@ -1,8 +1,8 @@
 use std::{fs, path::Path};
 
-use crate::dto::SolvedSolutionDto;
+use icfpc2022::{dto::SolvedSolutionDto, error::Result};
 
-pub fn stats(problems_n: &[String], solvers: &[String]) -> Result<(), std::io::Error> {
+pub fn stats(problems_n: &[String], solvers: &[String]) -> Result<()> {
     let mut sum_best: u64 = 0;
 
     for n in problems_n {
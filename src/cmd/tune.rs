@@ -0,0 +1,265 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rand::seq::index;
+use rayon::prelude::*;
+
+use icfpc2022::{
+    dto::TuneResultDto,
+    error::{Error, Result},
+    moves::Move,
+    solvers::{create_solver, seeded_rng, Problem},
+};
+
+#[derive(Debug, Clone)]
+pub struct TuneOptions {
+    /// Parameter values as `key=v1,v2,...`, replacing the default values of the key
+    pub params: Vec<String>,
+    /// Try this many random configurations instead of the whole grid
+    pub random: Option<usize>,
+    pub seed: Option<u64>,
+    pub output_dir: PathBuf,
+}
+
+type Space = Vec<(String, Vec<String>)>;
+
+/// The values tried for every solver when none are given on the command line
+fn default_space(solver: &str) -> Space {
+    let space: &[(&str, &[&str])] = match solver {
+        s if s.starts_with("annealing") => &[
+            ("step", &["4", "6", "8", "10", "14", "20"]),
+            ("iterations", &["2000", "5000", "10000"]),
+            ("restarts", &["0", "1", "2", "4"]),
+            ("reheats", &["0", "1", "2"]),
+        ],
+        "beam" | "beam_wide" => &[
+            ("width", &["4", "8", "16", "32"]),
+            ("step", &["5", "10", "20"]),
+        ],
+        "beam_edges" => &[
+            ("width", &["4", "8", "16", "32"]),
+            ("edges", &["4", "8", "16", "24"]),
+        ],
+        "divide_conquer" => &[
+            ("min_move_cost", &["50", "100", "200"]),
+            ("max_move_cost", &["500", "1000", "2000"]),
+            ("move_cost_step", &["50", "100", "200"]),
+        ],
+        "dp" | "dp_coarse" => &[("grid", &["8", "12", "16", "20", "24"])],
+        "genetic" => &[
+            ("population", &["32", "64", "128"]),
+            ("generations", &["100", "300", "1000"]),
+        ],
+        "mcts" => &[("step", &["5", "10", "20"]), ("width", &["4", "8", "16"])],
+        "segment" => &[
+            ("colors", &["4", "6", "8", "12", "16"]),
+            ("max_coords", &["8", "12", "16", "24"]),
+        ],
+        "simple_edges" => &[("edges", &["4", "8", "12", "16", "24", "32"])],
+        s if s.starts_with("simple") => &[
+            ("step", &["1", "2", "4"]),
+            ("xstep", &["5", "10", "20", "40"]),
+        ],
+        "source_reuse" => &[("max_depth", &["1", "2", "3", "4"])],
+        "strips" => &[("step", &["2", "4", "8"])],
+        "tempering" => &[
+            ("chains", &["4", "8", "16"]),
+            ("iterations", &["2000", "5000", "10000"]),
+            ("step", &["5", "10", "20"]),
+        ],
+        "tiles" => &[("max_edges", &["4", "8", "12", "16"])],
+        _ => &[],
+    };
+    space
+        .iter()
+        .map(|(key, values)| {
+            (
+                key.to_string(),
+                values.iter().map(|v| v.to_string()).collect(),
+            )
+        })
+        .collect()
+}
+
+fn parse_space(solver: &str, params: &[String]) -> Result<Space> {
+    let mut space = default_space(solver);
+    for param in params {
+        let (key, values) = param
+            .split_once('=')
+            .ok_or_else(|| Error::InvalidParameter(format!("`{param}` isn't key=v1,v2,...")))?;
+        let values: Vec<String> = values.split(',').map(|v| v.to_owned()).collect();
+        match space.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = values,
+            // the parameters are shared by all the solvers being tuned
+            None => match create_solver(None, &format!("{solver}@{key}={}", values[0])) {
+                Ok(_) => space.push((key.to_owned(), values)),
+                Err(e) => eprintln!("ignoring `{key}` for `{solver}`: {e}"),
+            },
+        }
+    }
+    Ok(space)
+}
+
+/// The solver spec of a configuration, given as the index of every value
+fn spec(solver: &str, space: &Space, mut config: usize) -> String {
+    let mut params = vec![];
+    for (key, values) in space {
+        params.push(format!("{key}={}", values[config % values.len()]));
+        config /= values.len();
+    }
+    if params.is_empty() {
+        solver.to_owned()
+    } else {
+        format!("{solver}@{}", params.join(","))
+    }
+}
+
+/// Runs every solver on the problems with each configuration of its parameter space,
+/// appending the results to `<output_dir>/<solver>.jsonl`. The best configurations
+/// are reported as solver specs, which work as solver names everywhere else. Chains
+/// are rejected, as their parameters would go to their last part.
+pub fn tune(
+    input_moves: Option<Vec<Move>>,
+    problem_paths: &[PathBuf],
+    solvers: &[String],
+    options: &TuneOptions,
+) -> Result<()> {
+    let problems = problem_paths
+        .iter()
+        .map(Problem::load)
+        .collect::<Result<Vec<_>>>()?;
+    std::fs::create_dir_all(&options.output_dir)?;
+    let mut rng = seeded_rng(options.seed);
+
+    for solver in solvers {
+        if solver.contains(['+', '%']) {
+            return Err(Error::InvalidParameter(format!(
+                "can't tune the chain `{solver}`, tune its solvers one by one instead"
+            )));
+        }
+        let space = parse_space(solver, &options.params)?;
+        let size: usize = space.iter().map(|(_, values)| values.len()).product();
+        let configs = match options.random {
+            Some(n) if n < size => index::sample(&mut rng, size, n).into_vec(),
+            _ => (0..size).collect(),
+        };
+        // the defaults go first, as the baseline
+        let mut specs = vec![solver.clone()];
+        specs.extend(configs.into_iter().map(|c| spec(solver, &space, c)));
+        println!("tuning {solver}: {} configurations", specs.len());
+
+        let results = evaluate(
+            &input_moves,
+            &problems,
+            &specs,
+            &options.output_dir.join(solver),
+        )?;
+        report(&problems, &specs, &results);
+    }
+    Ok(())
+}
+
+fn evaluate(
+    input_moves: &Option<Vec<Move>>,
+    problems: &[Problem],
+    specs: &[String],
+    log_path: &Path,
+) -> Result<Vec<TuneResultDto>> {
+    let log = Mutex::new(
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path.with_extension("jsonl"))?,
+    );
+    let runs: Vec<(&String, &Problem)> = specs
+        .iter()
+        .flat_map(|spec| problems.iter().map(move |p| (spec, p)))
+        .collect();
+    runs.par_iter()
+        .filter_map(|&(spec, problem)| {
+            let run = || -> Result<TuneResultDto> {
+                let solver = create_solver(input_moves.clone(), spec)?;
+                let mut canvas = problem.initial_canvas.clone();
+                let solution = solver.solve(&mut canvas, &problem.reference_painting)?;
+                let score = problem.reference_painting.calculate_score(&solution.result);
+                let result = TuneResultDto {
+                    spec: spec.clone(),
+                    problem: problem.id.clone(),
+                    total_score: (score + solution.cost).0,
+                    score: score.0,
+                    solution_cost: solution.cost.0,
+                };
+                writeln!(log.lock().unwrap(), "{}", serde_json::to_string(&result)?)?;
+                Ok(result)
+            };
+            match run() {
+                Ok(result) => Some(Ok(result)),
+                // a bad configuration shouldn't prevent the others from running
+                Err(e @ Error::Io(_)) => Some(Err(e)),
+                Err(e) => {
+                    eprintln!(
+                        "{:15}{spec}: FAILED: {e}",
+                        format!("[problem {}]", problem.id)
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn report(problems: &[Problem], specs: &[String], results: &[TuneResultDto]) {
+    let score = |spec: &str, problem: &str| {
+        results
+            .iter()
+            .find(|r| r.spec == spec && r.problem == problem)
+            .map(|r| r.total_score)
+    };
+    let default = &specs[0];
+
+    for problem in problems {
+        let best = results
+            .iter()
+            .filter(|r| r.problem == problem.id)
+            .min_by_key(|r| r.total_score);
+        if let Some(best) = best {
+            let baseline = score(default, &problem.id).map_or("-".to_owned(), |s| s.to_string());
+            println!(
+                "{:15}{}: {} (defaults: {baseline})",
+                format!("[problem {}]", problem.id),
+                best.spec,
+                best.total_score
+            );
+        }
+    }
+
+    // only the configurations that solved every problem compete overall
+    let mut sums: HashMap<&str, (u64, usize)> = HashMap::new();
+    for r in results {
+        let (sum, count) = sums.entry(r.spec.as_str()).or_default();
+        *sum += r.total_score;
+        *count += 1;
+    }
+    let best = specs
+        .iter()
+        .filter_map(|spec| match sums.get(spec.as_str()) {
+            Some(&(sum, count)) if count == problems.len() => Some((sum, spec)),
+            _ => None,
+        })
+        .min();
+    match best {
+        Some((sum, spec)) => {
+            let baseline = sums
+                .get(default.as_str())
+                .filter(|(_, count)| *count == problems.len())
+                .map_or("-".to_owned(), |(sum, _)| sum.to_string());
+            println!("best overall: {spec}: {sum} (defaults: {baseline})");
+        }
+        None => println!("no configuration solved every problem"),
+    }
+}
@@ -1,6 +1,10 @@
 use na::{Point4, Vector4};
 use std::{collections::HashMap, fmt::Display};
 
+/// Convergence threshold and iteration limit of the geometric median of `Color::optimal`
+const MEDIAN_EPS: f64 = 1e-3;
+const MEDIAN_ITERATIONS: u32 = 100;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Color(pub Point4<u8>);
 
@@ -79,6 +83,84 @@ impl Color {
         )
     }
 
+    /// The integer color with the lowest total distance to the counted colors, which
+    /// is their similarity to a block of that color: the geometric median, then its
+    /// best integer neighbour (every channel off by at most one) until none improves
+    pub fn optimal(counts: &HashMap<Color, u32>) -> Self {
+        if counts.is_empty() {
+            return Color::new(255, 255, 255, 255);
+        }
+        let counts: Vec<([f64; 4], f64)> = counts
+            .iter()
+            .map(|(c, n)| ([c.r(), c.g(), c.b(), c.a()].map(|v| v as f64), *n as f64))
+            .collect();
+        let distance = |a: &[f64; 4], b: &[f64; 4]| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        };
+        let total = |c: &[f64; 4]| -> f64 { counts.iter().map(|(p, n)| n * distance(p, c)).sum() };
+
+        // Weiszfeld's algorithm, starting from the average
+        let weight: f64 = counts.iter().map(|(_, n)| n).sum();
+        let mut median = [0.0; 4];
+        for (p, n) in &counts {
+            for (m, v) in median.iter_mut().zip(p) {
+                *m += n * v / weight;
+            }
+        }
+        for _ in 0..MEDIAN_ITERATIONS {
+            let (mut next, mut weights) = ([0.0; 4], 0.0);
+            for (p, n) in &counts {
+                let d = distance(p, &median);
+                if d > MEDIAN_EPS {
+                    for (m, v) in next.iter_mut().zip(p) {
+                        *m += n * v / d;
+                    }
+                    weights += n / d;
+                }
+            }
+            if weights == 0.0 {
+                break;
+            }
+            next = next.map(|v| v / weights);
+            let moved = distance(&next, &median);
+            median = next;
+            if moved < MEDIAN_EPS {
+                break;
+            }
+        }
+
+        let mut best = median.map(|v| v.round().clamp(0.0, 255.0));
+        let mut best_total = total(&best);
+        loop {
+            let mut improved = false;
+            let center = best;
+            for i in 0..81 {
+                let mut c = center;
+                let mut i = i;
+                for v in c.iter_mut() {
+                    *v += (i % 3) as f64 - 1.0;
+                    i /= 3;
+                }
+                if c.iter().any(|v| !(0.0..=255.0).contains(v)) {
+                    continue;
+                }
+                let t = total(&c);
+                if t < best_total {
+                    (best, best_total, improved) = (c, t, true);
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+        let [r, g, b, a] = best.map(|v| v as u8);
+        Color::new(r, g, b, a)
+    }
+
     /// taken from https://github.com/liborty/rstats
     pub fn gmedian(colors: &Vec<Color>, eps: f32, max_iterations: u32) -> Self {
         if colors.is_empty() {
@@ -198,3 +280,34 @@ fn find_centroid(colors: &Vec<Color>) -> Point4<f32> {
     ]
     .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block::Rect, painting::Painting};
+
+    #[test]
+    fn optimal_color_beats_its_surroundings() {
+        let data = vec![
+            Color::new(10, 200, 30, 255),
+            Color::new(12, 180, 90, 255),
+            Color::new(250, 0, 30, 128),
+            Color::new(11, 190, 35, 255),
+            Color::new(40, 100, 60, 200),
+            Color::new(10, 200, 30, 255),
+        ];
+        let painting = Painting::new(3, 2, data);
+        let r = Rect::from_coords([0, 0, 3, 2]);
+        let best = painting.optimal_color(&r);
+        let best_score = painting.calculate_score_rect(&r, best);
+        for i in 0..9u32.pow(4) {
+            let mut c = best;
+            let mut i = i;
+            for v in c.0.iter_mut() {
+                *v = (*v as i32 + (i % 9) as i32 - 4).clamp(0, 255) as u8;
+                i /= 9;
+            }
+            assert!(best_score <= painting.calculate_score_rect(&r, c) + 1e-9);
+        }
+    }
+}
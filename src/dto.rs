@@ -48,3 +48,14 @@ impl SolvedSolutionDto {
         }
     }
 }
+
+/// One run of the `tune` command
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TuneResultDto {
+    /// The solver along with its parameters, usable as a solver name
+    pub spec: String,
+    pub problem: String,
+    pub total_score: u64,
+    pub score: u64,
+    pub solution_cost: u64,
+}
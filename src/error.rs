@@ -0,0 +1,114 @@
+use std::fmt::Display;
+
+use crate::moves::MoveError;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Image(image::ImageError),
+    Png(png::EncodingError),
+    Move(MoveError),
+    /// Malformed ISL input
+    Parse(String),
+    /// A problem description that can't be turned into a canvas
+    InvalidProblem(String),
+    UnknownSolver(String),
+    UnknownProcessor(String),
+    /// A malformed or unknown solver parameter
+    InvalidParameter(String),
+    /// A solver or processor that can't handle its input
+    Solver(String),
+    Context(String, Box<Error>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+            Error::Context(_, e) => e.is_not_found(),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {e}"),
+            Error::Json(e) => write!(f, "JSON error: {e}"),
+            Error::Image(e) => write!(f, "image error: {e}"),
+            Error::Png(e) => write!(f, "PNG error: {e}"),
+            Error::Move(e) => write!(f, "move error: {e}"),
+            Error::Parse(msg) => write!(f, "parse error: {msg}"),
+            Error::InvalidProblem(msg) => write!(f, "invalid problem: {msg}"),
+            Error::UnknownSolver(name) => write!(f, "unknown solver `{name}`"),
+            Error::UnknownProcessor(name) => write!(f, "unknown processor `{name}`"),
+            Error::InvalidParameter(msg) => write!(f, "invalid parameter: {msg}"),
+            Error::Solver(msg) => write!(f, "solver error: {msg}"),
+            Error::Context(context, e) => write!(f, "{context}: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::Png(e) => Some(e),
+            Error::Move(e) => Some(e),
+            Error::Context(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Self {
+        Error::Png(e)
+    }
+}
+
+impl From<MoveError> for Error {
+    fn from(e: MoveError) -> Self {
+        Error::Move(e)
+    }
+}
+
+/// Attach a description of what was being done to an error
+pub trait ResultExt<T> {
+    fn context<C: Display>(self, context: C) -> Result<T>;
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for std::result::Result<T, E> {
+    fn context<C: Display>(self, context: C) -> Result<T> {
+        self.map_err(|e| Error::Context(context.to_string(), Box::new(e.into())))
+    }
+
+    fn with_context<C: Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|e| Error::Context(f().to_string(), Box::new(e.into())))
+    }
+}
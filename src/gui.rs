@@ -7,7 +7,9 @@ use raylib::prelude::*;
 use crate::{
     block::{BlockData, Point, Rect},
     canvas::Canvas,
+    error::{Result, ResultExt},
     moves::{AppliedMove, Cost, Move, Orientation, UndoMove},
+    overlay::{abs_diff_gradient, abs_diff_heatmap},
     painting::Painting,
     program::{self, to_isl},
 };
@@ -18,14 +20,6 @@ impl From<crate::color::Color> for raylib::ffi::Color {
     }
 }
 
-struct GradientColor(colorgrad::Color);
-impl From<GradientColor> for raylib::ffi::Color {
-    fn from(GradientColor(c): GradientColor) -> Self {
-        let c = c.to_rgba8();
-        raylib::prelude::Color::new(c[0], c[1], c[2], c[3]).into()
-    }
-}
-
 #[derive(PartialEq, Eq)]
 enum Tool {
     CutVert,
@@ -75,13 +69,12 @@ const TARGET_RECT: Rect = Rect::new(
     ),
 );
 
-pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path) {
-    let painting = Painting::load(problem_path);
+pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path) -> Result<()> {
+    let painting = Painting::load(problem_path)?;
     let initial_config_path = problem_path.with_extension("json");
-    let mut canvas = Canvas::try_create(initial_config_path, &painting)
-        .expect("gui_main: Error while creating Canvas");
+    let mut canvas = Canvas::try_create(initial_config_path, &painting)?;
     let initial_painting_score = painting.calculate_score_canvas(&canvas);
-    let mut current_worst_block_id = painting.find_worst_block_id(&canvas).clone();
+    let mut current_worst_block_id = painting.find_worst_block_id(&canvas).cloned();
     let mut show_alt_data = false;
 
     let (mut rl, thread) = raylib::init()
@@ -99,14 +92,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
         }
     }
     let target_texture = rl.load_texture_from_image(&thread, &target_image).unwrap();
-    let abs_diff_gradient = colorgrad::CustomGradient::new()
-        .colors(&[
-            colorgrad::Color::from_rgba8(255, 255, 255, 255),
-            colorgrad::Color::from_rgba8(255, 0, 0, 255),
-        ])
-        .domain(&[0.0, 500.0])
-        .build()
-        .unwrap();
+    let abs_diff_gradient = abs_diff_gradient();
     let mut current_abs_diff_texture = rl
         .load_texture_from_image(
             &thread,
@@ -122,11 +108,9 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
     let mut moves: Vec<AppliedMove> = match input_moves {
         Some(input_moves) => input_moves
             .into_iter()
-            .map(|mov| {
-                mov.apply(&mut canvas)
-                    .expect("failed to apply an inital move. were these moves for this canvas?")
-            })
-            .collect(),
+            .map(|mov| mov.apply(&mut canvas))
+            .collect::<std::result::Result<_, _>>()
+            .context("failed to apply an inital move. were these moves for this canvas?")?,
         None => vec![],
     };
 
@@ -197,7 +181,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
                         marked_block = None;
                         current_painting_score = painting.calculate_score_canvas(&canvas);
                         current_tool_score -= applied_move.cost;
-                        current_worst_block_id = painting.find_worst_block_id(&canvas).clone();
+                        current_worst_block_id = painting.find_worst_block_id(&canvas).cloned();
                         current_abs_diff_texture = rl
                             .load_texture_from_image(
                                 &thread,
@@ -212,7 +196,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
                         result.push(am.mov.clone());
                     }
                     program::write_to_file(&std::path::PathBuf::from("./manual.txt"), &result)
-                        .expect("Failed to write the manual solution file");
+                        .context("failed to write the manual solution file")?;
                 }
                 KeyboardKey::KEY_TAB => {
                     show_alt_data = !show_alt_data;
@@ -255,7 +239,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
                     marked_block = None;
                     current_painting_score = painting.calculate_score_canvas(&canvas);
                     current_tool_score += applied_move.cost;
-                    current_worst_block_id = painting.find_worst_block_id(&canvas).clone();
+                    current_worst_block_id = painting.find_worst_block_id(&canvas).cloned();
                     current_abs_diff_texture = rl
                         .load_texture_from_image(
                             &thread,
@@ -332,7 +316,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
                         MARGIN + b.r.bottom_left.y as i32,
                         b.r.width() as i32,
                         b.r.height() as i32,
-                        if id == &current_worst_block_id {
+                        if Some(id) == current_worst_block_id.as_ref() {
                             COLOR_WORST_BLOCK
                         } else {
                             COLOR_BLOCK_BORDER
@@ -355,7 +339,7 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
                         MARGIN + b.r.bottom_left.y as i32,
                         b.r.width() as i32,
                         b.r.height() as i32,
-                        if id == &current_worst_block_id {
+                        if Some(id) == current_worst_block_id.as_ref() {
                             COLOR_WORST_BLOCK
                         } else {
                             COLOR_BLOCK_BORDER
@@ -469,18 +453,17 @@ pub fn gui_main(input_moves: Option<Vec<Move>>, problem_path: &std::path::Path)
             Color::BLACK,
         );
     }
+    Ok(())
 }
 
 fn generate_abs_diff_image(canvas: &Canvas, painting: &Painting, gradient: &Gradient) -> Image {
     let width = painting.width() as i32;
     let height = painting.height() as i32;
     let mut target_image = Image::gen_image_color(width, height, Color::BLACK);
-    let abs_diff_map = painting.calculate_abs_diff_map(canvas);
+    let heatmap = abs_diff_heatmap(canvas, painting, gradient);
     for x in 0..painting.width() {
         for y in 0..painting.height() {
-            let v = abs_diff_map[(x + (y * painting.width)) as usize];
-            let c = GradientColor(gradient.at(v));
-            target_image.draw_pixel(x as i32, y as i32, c);
+            target_image.draw_pixel(x as i32, y as i32, heatmap.get_color(x, y));
         }
     }
     target_image
@@ -0,0 +1,32 @@
+//! The painting engine, ISL parser and solvers, shared by the CLI and other tools.
+#![feature(get_mut_unchecked)]
+
+extern crate derive_more;
+extern crate nalgebra as na;
+extern crate nom;
+
+pub mod block;
+pub mod canvas;
+pub mod color;
+pub mod dto;
+pub mod error;
+#[cfg(feature = "gui")]
+pub mod gui;
+mod helpers;
+pub mod moves;
+pub mod overlay;
+pub mod painting;
+pub mod parser;
+pub mod program;
+pub mod solvers;
+
+pub use block::{BlockId, Point, Rect};
+pub use canvas::Canvas;
+pub use color::Color;
+pub use error::{Error, Result};
+pub use helpers::os_str_to_str;
+pub use moves::{AppliedMove, Cost, Move, MoveError, Orientation};
+pub use painting::Painting;
+pub use parser::parse_moves_from_file;
+pub use program::generate_isl;
+pub use solvers::{create_solver, Problem, Solution, Solver, SOLVERS};
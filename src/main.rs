@@ -1,34 +1,23 @@
-#![feature(get_mut_unchecked)]
-
-extern crate derive_more;
-extern crate nalgebra as na;
-extern crate nom;
+mod cmd;
 
-use std::{ffi::OsString, fs::DirEntry, path::PathBuf};
+use std::{ffi::OsString, fs::DirEntry, path::PathBuf, time::Duration};
 
 use clap::Parser;
 use cmd::default::*;
+use cmd::portfolio::*;
+use cmd::render::*;
+use cmd::replay::*;
 use cmd::stats::*;
+use cmd::tune::*;
 use cmd::Args;
 use cmd::Commands;
-use helpers::*;
-use parser::parse_moves_from_file;
-use solvers::SOLVERS;
+use icfpc2022::error::Result;
+use icfpc2022::moves::Move;
+use icfpc2022::os_str_to_str;
+use icfpc2022::parser::parse_moves_from_file;
+use icfpc2022::solvers::SOLVERS;
 
-mod block;
-mod canvas;
-mod cmd;
-mod color;
-mod dto;
-mod gui;
-mod helpers;
-mod moves;
-mod painting;
-mod parser;
-mod program;
-mod solvers;
-
-fn get_problem_paths(args: &Args, force_batch: bool) -> Result<Vec<PathBuf>, std::io::Error> {
+fn get_problem_paths(args: &Args, force_batch: bool) -> Result<Vec<PathBuf>> {
     if !args.problems.is_empty() {
         Ok(args
             .problems
@@ -42,9 +31,9 @@ fn get_problem_paths(args: &Args, force_batch: bool) -> Result<Vec<PathBuf>, std
     }
 }
 
-fn get_all_problem_paths() -> Result<Vec<PathBuf>, std::io::Error> {
+fn get_all_problem_paths() -> Result<Vec<PathBuf>> {
     let paths: Vec<PathBuf> = std::fs::read_dir("./problems")?
-        .collect::<Result<Vec<DirEntry>, _>>()?
+        .collect::<std::io::Result<Vec<DirEntry>>>()?
         .iter()
         .filter_map(|f| {
             let x = os_str_to_str(f.path().file_name());
@@ -69,6 +58,13 @@ fn get_solvers(args: &Args) -> Option<Vec<String>> {
     }
 }
 
+fn get_input_moves(args: &Args) -> Result<Option<Vec<Move>>> {
+    match &args.input_moves {
+        Some(input_moves_path) => Ok(Some(parse_moves_from_file(input_moves_path)?)),
+        None => Ok(None),
+    }
+}
+
 fn list_current_solvers() -> Vec<String> {
     let mut current_solvers = vec![];
     let solvers_dir = std::fs::read_dir(PathBuf::from("./solutions/current"))
@@ -91,7 +87,7 @@ fn list_current_solvers() -> Vec<String> {
     current_solvers
 }
 
-fn main() -> std::io::Result<()> {
+fn run() -> Result<()> {
     let args = Args::parse();
     let solvers = get_solvers(&args);
 
@@ -107,13 +103,63 @@ fn main() -> std::io::Result<()> {
             problems.sort_by_key(|x| x.parse::<u8>().unwrap());
             stats(&problems, &solvers.unwrap_or_else(list_current_solvers))
         }
-        _ => {
-            let input_moves = match &args.input_moves {
-                Some(input_moves_path) => Some(parse_moves_from_file(input_moves_path)?),
-                None => None,
+        Some(Commands::Render { output_dir }) => {
+            let input_moves = get_input_moves(&args)?;
+            let problem_paths = get_problem_paths(&args, false)?;
+            render(input_moves, &problem_paths, output_dir)
+        }
+        Some(Commands::Portfolio { budget, jobs }) => {
+            let input_moves = get_input_moves(&args)?;
+            let problem_paths = get_problem_paths(&args, true)?;
+            let options = PortfolioOptions {
+                budget: Duration::from_secs(*budget),
+                jobs: jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
             };
+            portfolio(input_moves, &problem_paths, &args.solvers, &options)
+        }
+        Some(Commands::Replay {
+            output_dir,
+            every,
+            format,
+            delay_ms,
+        }) => {
+            let input_moves = get_input_moves(&args)?;
+            let problem_paths = get_problem_paths(&args, false)?;
+            let options = ReplayOptions {
+                every: *every,
+                format: *format,
+                delay_ms: *delay_ms,
+            };
+            replay(input_moves, &problem_paths, output_dir, &options)
+        }
+        Some(Commands::Tune {
+            params,
+            random,
+            seed,
+            output_dir,
+        }) => {
+            let input_moves = get_input_moves(&args)?;
+            let problem_paths = get_problem_paths(&args, true)?;
+            let options = TuneOptions {
+                params: params.clone(),
+                random: *random,
+                seed: *seed,
+                output_dir: output_dir.clone(),
+            };
+            tune(input_moves, &problem_paths, &args.solvers, &options)
+        }
+        _ => {
+            let input_moves = get_input_moves(&args)?;
             let problem_paths = get_problem_paths(&args, false)?;
             default_command(input_moves, &problem_paths, solvers)
         }
     }
 }
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
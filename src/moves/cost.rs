@@ -4,8 +4,11 @@ use derive_more::{Add, AddAssign, Sub, SubAssign, Sum};
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Add, AddAssign, Sub, SubAssign, Sum)]
 pub struct Cost(pub u64);
 
+/// How much a unit of pixel distance weighs in the final score
+pub const SIMILARITY_FACTOR: f64 = 0.005;
+
 impl Cost {
     pub fn from_block_cost(block_cost: f64) -> Cost {
-        Cost((block_cost * 0.005).round() as u64)
+        Cost((block_cost * SIMILARITY_FACTOR).round() as u64)
     }
 }
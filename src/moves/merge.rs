@@ -7,6 +7,12 @@ pub fn merge(
     block_a_id: &BlockId,
     block_b_id: &BlockId,
 ) -> Result<(Cost, UndoMove), MoveError> {
+    if block_a_id == block_b_id {
+        return Err(MoveError::LogicError(format!(
+            "Can't merge block [{}] with itself",
+            block_a_id
+        )));
+    }
     let block_a = canvas.get_move_block(block_a_id)?;
     let block_b = canvas.get_move_block(block_b_id)?;
     let cost = canvas.compute_cost(
@@ -61,7 +61,7 @@ pub enum MoveType {
 }
 
 impl Canvas {
-    fn get_move_block(&self, block_id: &BlockId) -> Result<&Block, MoveError> {
+    pub(crate) fn get_move_block(&self, block_id: &BlockId) -> Result<&Block, MoveError> {
         match self.get_block(block_id) {
             Some(block) => Ok(block),
             None => Err(MoveError::LogicError(format!(
@@ -71,7 +71,7 @@ impl Canvas {
         }
     }
 
-    fn get_move_block_mut(&mut self, block_id: &BlockId) -> Result<&mut Block, MoveError> {
+    pub(crate) fn get_move_block_mut(&mut self, block_id: &BlockId) -> Result<&mut Block, MoveError> {
         match self.get_block_mut(block_id) {
             Some(block) => Ok(block),
             None => Err(MoveError::LogicError(format!(
@@ -81,7 +81,7 @@ impl Canvas {
         }
     }
 
-    fn remove_move_block(&mut self, block_id: &BlockId) -> Result<Block, MoveError> {
+    pub(crate) fn remove_move_block(&mut self, block_id: &BlockId) -> Result<Block, MoveError> {
         match self.remove_block(block_id) {
             Some(block) => Ok(block),
             None => Err(MoveError::LogicError(format!(
@@ -98,6 +98,17 @@ pub enum MoveError {
     InvalidInput(String),
 }
 
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::LogicError(msg) => write!(f, "{msg}"),
+            MoveError::InvalidInput(msg) => write!(f, "invalid input: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
 impl Move {
     pub fn apply(self, canvas: &mut Canvas) -> Result<AppliedMove, MoveError> {
         use color::*;
@@ -125,3 +125,38 @@ fn test_color() -> Result<(), MoveError> {
     Move::Color("1".into(), Color::new(2, 2, 3, 4)).checked_apply(&mut canvas)?;
     Ok(())
 }
+
+#[test]
+fn merge_with_itself_fails() {
+    let mut canvas = make_complicated_canvas();
+    let res = Move::Merge("0.0".into(), "0.0".into()).apply(&mut canvas);
+    assert!(matches!(res, Err(MoveError::LogicError(_))));
+    assert_eq!(&canvas, &make_complicated_canvas());
+}
+
+#[test]
+fn point_cut_next_to_complex_edge() -> Result<(), MoveError> {
+    let mut canvas = make_cross_canvas();
+    let am = Move::PointCut("3.2".into(), 31, 31).checked_apply(&mut canvas)?;
+    let covered: u32 = am
+        .created_blocks()
+        .iter()
+        .map(|id| match &canvas.get_block(id).unwrap().data {
+            BlockData::Complex(bs) => bs.iter().map(|b| b.r.area()).sum::<u32>(),
+            BlockData::Simple(_) => unreachable!(),
+        })
+        .sum();
+    assert_eq!(covered, 16 * 16);
+    Ok(())
+}
+
+#[test]
+fn swap_moves_contents_like_moved_to() -> Result<(), MoveError> {
+    let mut canvas = make_cross_canvas();
+    let content = canvas.get_block(&"3.2".into()).unwrap().clone();
+    let position = canvas.get_block(&"3.0".into()).unwrap().r;
+    Move::Swap("3.0".into(), "3.2".into()).checked_apply(&mut canvas)?;
+    let swapped = canvas.get_block(&"3.0".into()).unwrap();
+    assert_eq!(content.moved_to(position.bottom_left).data, swapped.data);
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use colorgrad::{CustomGradient, Gradient};
+
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    painting::Painting,
+};
+
+pub fn block_border_color() -> Color {
+    Color::new(128, 128, 128, 255)
+}
+
+pub fn highlight_color() -> Color {
+    Color::new(255, 0, 0, 255)
+}
+
+pub fn abs_diff_gradient() -> Gradient {
+    CustomGradient::new()
+        .colors(&[
+            colorgrad::Color::from_rgba8(255, 255, 255, 255),
+            colorgrad::Color::from_rgba8(255, 0, 0, 255),
+        ])
+        .domain(&[0.0, 500.0])
+        .build()
+        .unwrap()
+}
+
+/// Per-pixel distance between the canvas and the target, mapped through the gradient
+pub fn abs_diff_heatmap(canvas: &Canvas, painting: &Painting, gradient: &Gradient) -> Painting {
+    let data = painting
+        .calculate_abs_diff_map(canvas)
+        .into_iter()
+        .map(|v| {
+            let [r, g, b, a] = gradient.at(v).to_rgba8();
+            Color::new(r, g, b, a)
+        })
+        .collect();
+    Painting::new(painting.width(), painting.height(), data)
+}
+
+/// The rendered canvas with every block outlined, and the given blocks highlighted
+pub fn blocks_overlay(canvas: &Canvas, highlighted: &[BlockId]) -> Painting {
+    let mut result = canvas.render();
+    for b in canvas.blocks_iter() {
+        draw_rect_lines(&mut result, &b.r, block_border_color());
+    }
+    // draw the highlight last so that neighbouring borders don't cover it
+    for b in highlighted.iter().filter_map(|id| canvas.get_block(id)) {
+        draw_rect_lines(&mut result, &b.r, highlight_color());
+    }
+    result
+}
+
+pub fn draw_rect_lines(painting: &mut Painting, r: &Rect, c: Color) {
+    if r.area() == 0 {
+        return;
+    }
+    let right = r.top_right.x - 1;
+    let top = r.top_right.y - 1;
+    for x in r.x()..r.top_right.x {
+        painting.set_color(x, r.y(), c);
+        painting.set_color(x, top, c);
+    }
+    for y in r.y()..r.top_right.y {
+        painting.set_color(r.x(), y, c);
+        painting.set_color(right, y, c);
+    }
+}
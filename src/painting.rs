@@ -1,6 +1,7 @@
 use crate::block::{Block, BlockData, BlockId, Rect};
 use crate::canvas::Canvas;
 use crate::color::Color;
+use crate::error::{Result, ResultExt};
 use crate::moves::Cost;
 use image::{Rgba, RgbaImage};
 use std::collections::HashMap;
@@ -53,15 +54,12 @@ impl Painting {
         }
     }
 
-    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Self {
-        let file = match File::open(&path) {
-            Err(why) => panic!("couldn't open {:?}: {}", path, why),
-            Ok(file) => file,
-        };
+    pub fn load<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<Self> {
+        let file = File::open(&path).with_context(|| format!("couldn't open {:?}", path))?;
         let mut reader = BufReader::new(file);
-        let dyn_img =
-            image::load(&mut reader, image::ImageFormat::Png).expect("image loading failed");
-        Painting::from_image(dyn_img.into_rgba8())
+        let dyn_img = image::load(&mut reader, image::ImageFormat::Png)
+            .with_context(|| format!("couldn't load {:?}", path))?;
+        Ok(Painting::from_image(dyn_img.into_rgba8()))
     }
 
     pub fn width(&self) -> u32 {
@@ -76,6 +74,10 @@ impl Painting {
         self.data[(x + y * self.width) as usize]
     }
 
+    pub fn set_color(&mut self, x: u32, y: u32, c: Color) {
+        self.data[(x + y * self.width) as usize] = c;
+    }
+
     pub fn count_colors(&self, r: &Rect) -> HashMap<Color, u32> {
         let mut counts = HashMap::new();
         for x in r.x()..r.top_right.x {
@@ -124,7 +126,38 @@ impl Painting {
         )
     }
 
-    pub fn find_worst_block_id<'a>(&self, canvas: &'a Canvas) -> &'a BlockId {
+    /// The color scoring best on the rect, see `Color::optimal`
+    pub fn optimal_color(&self, r: &Rect) -> Color {
+        Color::optimal(&self.count_colors(r))
+    }
+
+    /// The painting at `1 / factor` of the resolution, every pixel averaging a square
+    /// of `factor` x `factor` pixels
+    pub fn downscale(&self, factor: u32) -> Painting {
+        let (width, height) = (self.width / factor, self.height / factor);
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let r =
+                    Rect::from_coords([x * factor, y * factor, (x + 1) * factor, (y + 1) * factor]);
+                data.push(self.calculate_average_color(&r));
+            }
+        }
+        Painting::new(width, height, data)
+    }
+
+    /// The painting downscaled 2x, 4x, and so on, `levels` times
+    pub fn pyramid(&self, levels: usize) -> Vec<Painting> {
+        let mut pyramid: Vec<Painting> = vec![];
+        for _ in 0..levels {
+            let next = pyramid.last().unwrap_or(self).downscale(2);
+            pyramid.push(next);
+        }
+        pyramid
+    }
+
+    /// `None` if every block matches the target exactly
+    pub fn find_worst_block_id<'a>(&self, canvas: &'a Canvas) -> Option<&'a BlockId> {
         let mut worst_block = None;
         let mut worst_score = 0.0;
         for b in canvas.blocks_iter() {
@@ -134,7 +167,7 @@ impl Painting {
                 worst_block = Some(&b.id);
             }
         }
-        worst_block.unwrap()
+        worst_block
     }
 
     pub fn calculate_abs_diff_map(&self, target: &Canvas) -> Vec<f64> {
@@ -235,14 +268,117 @@ impl Painting {
         pixel_score.sqrt()
     }
 
-    pub fn write_to_file(&self, path: &std::path::Path) {
+    pub fn to_image(&self) -> RgbaImage {
         let mut img = RgbaImage::new(self.width, self.height);
         for x in 0..self.width {
             for y in 0..self.height {
                 img.put_pixel(x, self.height - y - 1, self.get_color(x, y).into());
             }
         }
+        img
+    }
+
+    pub fn color_sums(&self) -> ColorSums {
+        ColorSums::new(self)
+    }
+
+    pub fn write_to_file(&self, path: &std::path::Path) -> Result<()> {
+        self.to_image()
+            .save_with_format(path, image::ImageFormat::Png)
+            .with_context(|| format!("couldn't write {}", path.display()))
+    }
+}
+
+/// Per-channel prefix sums (and sums of squares) of a painting, for constant
+/// time rectangle averages and variances
+pub struct ColorSums {
+    stride: u32,
+    sums: Vec<[u64; 4]>,
+    squares: Vec<[u64; 4]>,
+}
+
+impl ColorSums {
+    pub fn new(painting: &Painting) -> Self {
+        let stride = painting.width + 1;
+        let size = (stride * (painting.height + 1)) as usize;
+        let mut sums = vec![[0u64; 4]; size];
+        let mut squares = vec![[0u64; 4]; size];
+        for y in 0..painting.height {
+            let mut row = [0u64; 4];
+            let mut row_squares = [0u64; 4];
+            for x in 0..painting.width {
+                let c = painting.get_color(x, y);
+                for (ch, v) in [c.r(), c.g(), c.b(), c.a()].into_iter().enumerate() {
+                    row[ch] += v as u64;
+                    row_squares[ch] += v as u64 * v as u64;
+                }
+                let below = (x + 1 + y * stride) as usize;
+                let cur = (x + 1 + (y + 1) * stride) as usize;
+                sums[cur] = std::array::from_fn(|ch| row[ch] + sums[below][ch]);
+                squares[cur] = std::array::from_fn(|ch| row_squares[ch] + squares[below][ch]);
+            }
+        }
+        ColorSums {
+            stride,
+            sums,
+            squares,
+        }
+    }
+
+    fn rect_sum(&self, table: &[[u64; 4]], r: &Rect) -> [u64; 4] {
+        let at = |x: u32, y: u32| &table[(x + y * self.stride) as usize];
+        let tr = at(r.top_right.x, r.top_right.y);
+        let tl = at(r.x(), r.top_right.y);
+        let br = at(r.top_right.x, r.y());
+        let bl = at(r.x(), r.y());
+        std::array::from_fn(|ch| tr[ch] + bl[ch] - tl[ch] - br[ch])
+    }
+
+    pub fn sum(&self, r: &Rect) -> [u64; 4] {
+        self.rect_sum(&self.sums, r)
+    }
 
-        img.save_with_format(path, image::ImageFormat::Png).unwrap();
+    /// Sum of squared distances to the mean color, over all channels
+    pub fn squared_error(&self, r: &Rect) -> f64 {
+        let area = r.area() as f64;
+        if area == 0.0 {
+            return 0.0;
+        }
+        let sums = self.sum(r);
+        let squares = self.rect_sum(&self.squares, r);
+        sums.iter()
+            .zip(squares)
+            .map(|(&s, sq)| (sq as f64 - (s as f64).powi(2) / area).max(0.0))
+            .sum()
+    }
+
+    /// Sum of squared distances to the given color, over all channels
+    pub fn squared_distance(&self, r: &Rect, c: Color) -> f64 {
+        let area = r.area() as f64;
+        let sums = self.sum(r);
+        let squares = self.rect_sum(&self.squares, r);
+        [c.r(), c.g(), c.b(), c.a()]
+            .into_iter()
+            .zip(sums.iter().zip(squares))
+            .map(|(v, (&s, sq))| {
+                let v = v as f64;
+                (sq as f64 - 2.0 * v * s as f64 + area * v * v).max(0.0)
+            })
+            .sum()
+    }
+
+    /// Same as `Painting::calculate_average_color`, without walking the pixels
+    pub fn average(&self, r: &Rect) -> Color {
+        let area = r.area() as u64;
+        if area == 0 {
+            return Color::new(255, 255, 255, 255);
+        }
+        let [r, g, b, a] = self.sum(r);
+        Color::new(
+            (r / area) as u8,
+            (g / area) as u8,
+            (b / area) as u8,
+            (a / area) as u8,
+        )
     }
 }
@@ -4,6 +4,7 @@ use std::path::Path;
 
 use crate::block::BlockId;
 use crate::color::Color;
+use crate::error::{Error, Result, ResultExt};
 use crate::moves::{Move, Orientation};
 use nom::character::complete::{line_ending, one_of};
 use nom::combinator::opt;
@@ -112,20 +113,32 @@ pub fn parse_move_line(input: &str) -> IResult<&str, Move> {
     terminated(parse_move, opt(line_ending))(input)
 }
 
-pub fn parse_moves_from_file<P: AsRef<Path>>(file_path: P) -> std::io::Result<Vec<Move>> {
-    let file = File::open(file_path)?;
+pub fn parse_moves_from_file<P: AsRef<Path>>(file_path: P) -> Result<Vec<Move>> {
+    let file_path = file_path.as_ref();
+    let file =
+        File::open(file_path).with_context(|| format!("couldn't open {}", file_path.display()))?;
     let reader = BufReader::new(file);
 
     let mut moves = vec![];
-    for line_res in reader.lines() {
+    for (line_no, line_res) in reader.lines().enumerate() {
         let line = line_res?;
         let res = parse_move_line(line.as_str());
         match res {
             Ok(("", mov)) => moves.push(mov),
             Ok((remainder, _)) => {
-                panic!("parser finished before the end of the line: {line}, {remainder}")
+                return Err(Error::Parse(format!(
+                    "{}:{}: parser finished before the end of the line: {line}, {remainder}",
+                    file_path.display(),
+                    line_no + 1
+                )))
+            }
+            Err(err) => {
+                return Err(Error::Parse(format!(
+                    "{}:{}: failed to parse line {line:?}: {err}",
+                    file_path.display(),
+                    line_no + 1
+                )))
             }
-            Err(err) => panic!("failed to parse line {line:?}: {err}"),
         }
     }
     Ok(moves)
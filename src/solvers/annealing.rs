@@ -1,178 +1,405 @@
+use rand::{seq::SliceRandom, Rng};
+
 use crate::{
     block::Block,
     canvas::Canvas,
-    moves::{AppliedMove, Cost, Move, MoveType, Orientation, UndoMoveOp},
-    painting::Painting,
+    error::Result,
+    moves::{AppliedMove, Cost, Move, MoveType, Orientation},
+    painting::{ColorSums, Painting},
+};
+
+use super::{
+    auto_color,
+    cuts::{Cut, CutSource, EdgeProfile},
+    Solver,
 };
 
-use super::Solver;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    Linear,
+    Exponential,
+    /// Tracks a target acceptance rate that goes down over time
+    Adaptive,
+}
 
 #[derive(Clone)]
 pub struct Annealing {
+    pub name: String,
     pub step: u32,
+    /// When not zero, new cuts go along one of this many of the strongest edges of
+    /// the block instead of the grid
+    pub edges: usize,
+    pub schedule: Schedule,
+    pub iterations: u32,
+    /// How many more runs start over from the initial canvas, the best state of all
+    /// the runs being the result
+    pub restarts: u32,
+    /// How many times every run reheats to the starting temperature. A reheat
+    /// continues from the best state of the run rather than from scratch, trading
+    /// diversity for a deeper search around it.
+    pub reheats: u32,
 }
 
+/// The starting temperature, relative to the initial total score
+const T0_FRACTION: f64 = 0.01;
+/// The final temperature of the exponential schedule, relative to the starting one
+pub(super) const T_END_RATIO: f64 = 0.001;
+const ADAPTIVE_WINDOW: u32 = 100;
+const MAX_SHIFT: i64 = 10;
+const MAX_COLOR_SHIFT: i16 = 16;
+
 impl Solver for Annealing {
-    fn name(&self) -> &'static str {
-        if self.step == 10 {
-            "annealing"
-        } else if self.step == 4 {
-            "annealing_s4"
-        } else {
-            todo!("Implement this")
-        }
+    fn name(&self) -> &str {
+        &self.name
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove> {
-        // If default coloring is needed, use top_color+
-        let mut applied_moves = vec![];
-        let mut current_move_cost = Cost(0);
-        let mut current_painting_score = painting.calculate_score_canvas(canvas);
-        const KMAX: u32 = 5000;
-        for k in 0..KMAX {
-            let t = self.temperature(1.0 - (k as f32 + 1.0) / KMAX as f32);
-            let budget = (current_painting_score.0 - current_move_cost.0) as i64;
-            let mut iteration_canvas = canvas.clone();
-            let iteration_moves = self.pick_neighbor(
-                &mut iteration_canvas,
-                painting,
-                applied_moves.clone(),
-                budget,
-            );
-            let new_painting_score = painting.calculate_score_canvas(&iteration_canvas);
-            let new_move_cost = iteration_moves.iter().map(|am| am.cost).sum::<Cost>();
-            let e_curr = (current_painting_score + current_move_cost).0 as f32;
-            let e_new = (new_painting_score + new_move_cost).0 as f32;
-            if self.p(e_curr, e_new, t) >= rand::random::<f32>() {
-                *canvas = iteration_canvas;
-                applied_moves = iteration_moves;
-                current_move_cost = new_move_cost;
-                current_painting_score = new_painting_score;
-            }
-        }
-        applied_moves
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let neighborhood = Neighborhood {
+            initial: canvas.clone(),
+            painting,
+            sums: &sums,
+            source: CutSource::new(&profile, self.step, self.edges),
+        };
+        let best = self.anneal(&neighborhood, &mut rand::thread_rng());
+        neighborhood.apply_to(best.moves, canvas)
     }
 }
 
 impl Annealing {
-    fn temperature(&self, x: f32) -> f32 {
-        x
+    pub(super) fn initial_temperature(start: &State) -> f64 {
+        (start.total as f64 * T0_FRACTION).max(1.0)
     }
 
-    fn pick_neighbor(
-        &self,
-        canvas: &mut Canvas,
-        painting: &Painting,
-        mut moves: Vec<AppliedMove>,
-        budget: i64,
-    ) -> Vec<AppliedMove> {
-        let viable_moves = self.get_viable_moves(canvas, &moves, budget);
-        let (undo_count, mov) = &viable_moves[rand::random::<usize>() % viable_moves.len()];
-
-        for _ in 0..*undo_count {
-            let am = moves.pop().unwrap();
-            am.undo(canvas);
+    /// The best state of all the restarts, which share the iterations evenly
+    fn anneal<R: Rng>(&self, neighborhood: &Neighborhood, rng: &mut R) -> State {
+        let runs = (self.restarts + 1) * (self.reheats + 1);
+        let run_iterations = (self.iterations / runs).max(1);
+        let mut best = neighborhood.initial_state();
+        for _ in 0..=self.restarts {
+            let found = self.run(
+                neighborhood,
+                neighborhood.initial_state(),
+                run_iterations,
+                rng,
+            );
+            if found.total < best.total {
+                best = found;
+            }
         }
-        let applied_move = mov.clone().apply(canvas).expect("Failed to apply a move");
-        let undo_op = applied_move.undo.operation.clone();
-        moves.push(applied_move);
-
-        // Now automatically color if it makes sense to do so
-        match undo_op {
-            UndoMoveOp::Cut {
-                delete_block_ids, ..
-            } => {
-                for b_id in delete_block_ids {
-                    let b = canvas.get_block(&b_id).unwrap();
-                    let before = painting.calculate_score_canvas(canvas);
-                    let color = painting.calculate_average_color(&b.r);
-                    let mov = Move::Color(b_id, color);
-                    let am = mov.apply(canvas).unwrap();
-                    let after = painting.calculate_score_canvas(canvas);
-                    if (after.0 + am.cost.0) > before.0 {
-                        am.undo(canvas);
-                    } else {
-                        moves.push(am);
+        best
+    }
+
+    /// Anneals from `start`, then again from the best state after every reheat
+    fn run<R: Rng>(
+        &self,
+        neighborhood: &Neighborhood,
+        start: State,
+        run_iterations: u32,
+        rng: &mut R,
+    ) -> State {
+        let t0 = Self::initial_temperature(&start);
+        let mut best = start.clone();
+        let mut current = start;
+
+        for reheat in 0..=self.reheats {
+            if reheat > 0 {
+                current = best.clone();
+            }
+            let mut t = t0;
+            let mut accepted = 0;
+            for k in 0..run_iterations {
+                let progress = k as f64 / run_iterations as f64;
+                t = match self.schedule {
+                    Schedule::Linear => t0 * (1.0 - progress),
+                    Schedule::Exponential => t0 * T_END_RATIO.powf(progress),
+                    Schedule::Adaptive => t,
+                };
+
+                if let Some(candidate) = neighborhood.neighbor(&current, rng) {
+                    if accept(current.total, candidate.total, t, rng) {
+                        accepted += 1;
+                        current = candidate;
+                        if current.total < best.total {
+                            best = current.clone();
+                        }
                     }
                 }
+
+                if self.schedule == Schedule::Adaptive && (k + 1) % ADAPTIVE_WINDOW == 0 {
+                    let rate = accepted as f64 / ADAPTIVE_WINDOW as f64;
+                    let target = 0.5 * (1.0 - progress) + 0.01;
+                    t *= if rate > target { 0.8 } else { 1.25 };
+                    accepted = 0;
+                }
             }
-            _ => panic!("Missing move handler"),
         }
+        best
+    }
+}
 
-        moves
+/// Metropolis criterion
+pub(super) fn accept<R: Rng>(e_curr: u64, e_new: u64, t: f64, rng: &mut R) -> bool {
+    if e_new <= e_curr {
+        true
+    } else if t <= 0.0 {
+        false
+    } else {
+        rng.gen::<f64>() < (-((e_new - e_curr) as f64) / t).exp()
     }
+}
 
-    fn get_viable_moves(
-        &self,
-        canvas: &mut Canvas,
-        current_moves: &Vec<AppliedMove>,
-        mut budget: i64,
-    ) -> Vec<(u32, Move)> {
-        let mut moves = vec![];
-        if budget > 0 {
-            for b in canvas.blocks_iter() {
-                self.get_moves_for_block(b, canvas, budget, &mut moves, 0);
-            }
+fn creates_empty_block(canvas: &Canvas, am: &AppliedMove) -> bool {
+    am.created_blocks()
+        .iter()
+        .any(|id| canvas.get_block(id).is_none_or(|b| b.area() == 0))
+}
+
+/// A program along with the canvas it produces
+#[derive(Clone)]
+pub(super) struct State {
+    pub moves: Vec<Move>,
+    canvas: Canvas,
+    move_cost: Cost,
+    pub total: u64,
+}
+
+/// Proposes programs close to a given one
+pub(super) struct Neighborhood<'a> {
+    pub initial: Canvas,
+    pub painting: &'a Painting,
+    pub sums: &'a ColorSums,
+    pub source: CutSource<'a>,
+}
+
+impl<'a> Neighborhood<'a> {
+    pub fn initial_state(&self) -> State {
+        self.state(self.initial.clone(), vec![], Cost(0))
+    }
+
+    fn state(&self, canvas: Canvas, moves: Vec<Move>, move_cost: Cost) -> State {
+        let total = (self.painting.calculate_score_canvas(&canvas) + move_cost).0;
+        State {
+            moves,
+            canvas,
+            move_cost,
+            total,
         }
-        for i in 0..current_moves.len() {
-            let am = &current_moves[current_moves.len() - i - 1];
-            budget += am.cost.0 as i64;
-            if budget > 0 {
-                if let UndoMoveOp::Cut { restore_blocks, .. } = &am.undo.operation {
-                    for b in restore_blocks {
-                        self.get_moves_for_block(b, canvas, budget, &mut moves, i as u32 + 1);
-                    }
+    }
+
+    /// Applies the program to the canvas, which must be the initial one
+    pub fn apply_to(&self, moves: Vec<Move>, canvas: &mut Canvas) -> Result<Vec<AppliedMove>> {
+        let mut applied_moves = vec![];
+        for mov in moves {
+            applied_moves.push(mov.apply(canvas)?);
+        }
+        Ok(applied_moves)
+    }
+
+    /// Replays the program from the initial canvas, dropping the moves that no
+    /// longer apply. Refreshing sets every color to the average of its block.
+    fn replay(&self, moves: Vec<Move>, refresh_colors: bool) -> State {
+        let mut canvas = self.initial.clone();
+        let mut kept = vec![];
+        let mut move_cost = Cost(0);
+        for mov in moves {
+            let mov = match mov {
+                Move::Color(id, _) if refresh_colors => match canvas.get_block(&id) {
+                    Some(b) => Move::Color(id, self.sums.average(&b.r)),
+                    None => continue,
+                },
+                mov => mov,
+            };
+            if let Ok(am) = mov.clone().apply(&mut canvas) {
+                // shifted cuts can end up on the block border, which the engine accepts
+                if creates_empty_block(&canvas, &am) {
+                    am.undo(&mut canvas);
+                    continue;
                 }
+                move_cost += am.cost;
+                kept.push(mov);
             }
         }
-        moves
+        self.state(canvas, kept, move_cost)
     }
 
-    fn get_moves_for_block(
-        &self,
-        b: &Block,
-        canvas: &Canvas,
-        budget: i64,
-        moves: &mut Vec<(u32, Move)>,
-        undo_count: u32,
-    ) {
-        let step: u32 = self.step;
-        let xstep = self.step * 2;
+    /// Appends the move, and colors the blocks it creates when that pays off
+    fn extend(&self, state: &State, mov: Move) -> Option<State> {
+        let mut canvas = state.canvas.clone();
+        let mut moves = state.moves.clone();
+        let am = mov.clone().apply(&mut canvas).ok()?;
+        if creates_empty_block(&canvas, &am) {
+            return None;
+        }
+        let mut move_cost = state.move_cost + am.cost;
+        moves.push(mov);
+        for id in am.created_blocks() {
+            let b = canvas.get_block(&id)?;
+            if let (_, Some((c, _))) = auto_color(&canvas, self.painting, self.sums, b) {
+                let mov = Move::Color(id, c);
+                move_cost += mov.clone().apply(&mut canvas).ok()?.cost;
+                moves.push(mov);
+            }
+        }
+        Some(self.state(canvas, moves, move_cost))
+    }
 
+    pub fn neighbor<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        match rng.gen_range(0..6) {
+            0 | 1 => self.add_cut(state, rng),
+            2 => self.shift_cut(state, rng),
+            3 => self.recolor(state, rng),
+            4 => self.merge(state, rng),
+            _ => self.remove(state, rng),
+        }
+    }
+
+    fn random_block<'s, R: Rng>(&self, state: &'s State, rng: &mut R) -> Option<&'s Block> {
+        let count = state.canvas.blocks_iter().count();
+        if count == 0 {
+            return None;
+        }
+        state.canvas.blocks_iter().nth(rng.gen_range(0..count))
+    }
+
+    fn add_cut<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        let b = self.random_block(state, rng)?;
         let r = b.r;
-        let linear_cut_cost = canvas.compute_cost(MoveType::LineCut, r.area());
-        if (linear_cut_cost.0 as i64) < budget {
-            for x in (step..r.width() - 1).step_by(step as usize) {
-                moves.push((
-                    undo_count,
-                    Move::LineCut(b.id.clone(), Orientation::Vertical, r.x() + x),
-                ));
+        let (x, y, cross_x, cross_y) = match self.source {
+            CutSource::Grid(step, xstep) => {
+                // an offset inside the block, on the solver grid
+                let mut offset = |from: u32, len: u32, step: u32| {
+                    let slots = len.saturating_sub(1) / step;
+                    (slots > 0).then(|| from + step * rng.gen_range(1..=slots))
+                };
+                (
+                    offset(r.x(), r.width(), step),
+                    offset(r.y(), r.height(), step),
+                    offset(r.x(), r.width(), xstep),
+                    offset(r.y(), r.height(), xstep),
+                )
+            }
+            CutSource::Edges(profile, count) => {
+                let xs = profile.line_offsets(&r, Orientation::Vertical, count);
+                let ys = profile.line_offsets(&r, Orientation::Horizontal, count);
+                (
+                    xs.choose(rng).copied(),
+                    ys.choose(rng).copied(),
+                    xs.choose(rng).copied(),
+                    ys.choose(rng).copied(),
+                )
             }
-            for y in (step..r.height() - 1).step_by(step as usize) {
-                moves.push((
-                    undo_count,
-                    Move::LineCut(b.id.clone(), Orientation::Horizontal, r.y() + y),
-                ));
+            source => {
+                let cuts = source.cuts(&r);
+                let lines = |orientation| -> Vec<u32> {
+                    cuts.iter()
+                        .filter_map(|cut| match *cut {
+                            Cut::Line(o, offset) if o == orientation => Some(offset),
+                            _ => None,
+                        })
+                        .collect()
+                };
+                let (xs, ys) = (lines(Orientation::Vertical), lines(Orientation::Horizontal));
+                let points: Vec<(u32, u32)> = cuts
+                    .iter()
+                    .filter_map(|cut| match *cut {
+                        Cut::Point(x, y) => Some((x, y)),
+                        _ => None,
+                    })
+                    .collect();
+                let point = points.choose(rng).copied();
+                (
+                    xs.choose(rng).copied(),
+                    ys.choose(rng).copied(),
+                    point.map(|(x, _)| x),
+                    point.map(|(_, y)| y),
+                )
             }
+        };
+
+        let (mov, move_type) = match rng.gen_range(0..3) {
+            0 => (
+                Move::LineCut(b.id.clone(), Orientation::Vertical, x?),
+                MoveType::LineCut,
+            ),
+            1 => (
+                Move::LineCut(b.id.clone(), Orientation::Horizontal, y?),
+                MoveType::LineCut,
+            ),
+            _ => (
+                Move::PointCut(b.id.clone(), cross_x?, cross_y?),
+                MoveType::PointCut,
+            ),
+        };
+        // a cut that costs more than the whole remaining score can't pay off
+        let budget = state.total as i64 - 2 * state.move_cost.0 as i64;
+        if state.canvas.compute_cost(move_type, r.area()).0 as i64 >= budget {
+            return None;
         }
-        let cross_cut_cost = canvas.compute_cost(MoveType::PointCut, r.area());
-        if (cross_cut_cost.0 as i64) < budget {
-            for x in (xstep..r.width() - 1).step_by(xstep as usize) {
-                for y in (xstep..r.height() - 1).step_by(xstep as usize) {
-                    moves.push((
-                        undo_count,
-                        Move::PointCut(b.id.clone(), r.x() + x, r.y() + y),
-                    ));
-                }
+        self.extend(state, mov)
+    }
+
+    fn shift_cut<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        let cuts: Vec<usize> = (0..state.moves.len())
+            .filter(|&i| matches!(state.moves[i], Move::LineCut(..) | Move::PointCut(..)))
+            .collect();
+        let &i = cuts.get(rng.gen_range(0..cuts.len().max(1)))?;
+        let mut shift = |v: u32| (v as i64 + rng.gen_range(-MAX_SHIFT..=MAX_SHIFT)).max(0) as u32;
+        let mut moves = state.moves.clone();
+        moves[i] = match &moves[i] {
+            Move::LineCut(id, orientation, offset) => {
+                Move::LineCut(id.clone(), *orientation, shift(*offset))
+            }
+            Move::PointCut(id, x, y) => Move::PointCut(id.clone(), shift(*x), shift(*y)),
+            _ => unreachable!(),
+        };
+        Some(self.replay(moves, true))
+    }
+
+    fn recolor<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        let colors: Vec<usize> = (0..state.moves.len())
+            .filter(|&i| matches!(state.moves[i], Move::Color(..)))
+            .collect();
+        let &i = colors.get(rng.gen_range(0..colors.len().max(1)))?;
+        let mut moves = state.moves.clone();
+        if let Move::Color(_, c) = &mut moves[i] {
+            for ch in c.0.iter_mut() {
+                let delta = rng.gen_range(-MAX_COLOR_SHIFT..=MAX_COLOR_SHIFT);
+                *ch = (*ch as i16 + delta).clamp(0, 255) as u8;
             }
         }
+        Some(self.replay(moves, false))
+    }
+
+    fn merge<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        let a = self.random_block(state, rng)?;
+        let (ra, ax, ay) = (a.r, a.r.x(), a.r.y());
+        let neighbours: Vec<&Block> = state
+            .canvas
+            .blocks_iter()
+            .filter(|b| b.id != a.id)
+            .filter(|b| {
+                let rb = b.r;
+                let side_by_side = ay == rb.y()
+                    && ra.top_right.y == rb.top_right.y
+                    && (ra.top_right.x == rb.x() || rb.top_right.x == ax);
+                let stacked = ax == rb.x()
+                    && ra.top_right.x == rb.top_right.x
+                    && (ra.top_right.y == rb.y() || rb.top_right.y == ay);
+                side_by_side || stacked
+            })
+            .collect();
+        let b = neighbours.get(rng.gen_range(0..neighbours.len().max(1)))?;
+        self.extend(state, Move::Merge(a.id.clone(), b.id.clone()))
     }
 
-    fn p(&self, e_curr: f32, e_new: f32, t: f32) -> f32 {
-        if e_new < e_curr {
-            1.0
-        } else {
-            ((-(e_new - e_curr)) / t).exp()
+    fn remove<R: Rng>(&self, state: &State, rng: &mut R) -> Option<State> {
+        if state.moves.is_empty() {
+            return None;
         }
+        let mut moves = state.moves.clone();
+        moves.remove(rng.gen_range(0..moves.len()));
+        Some(self.replay(moves, false))
     }
 }
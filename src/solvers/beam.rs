@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use crate::{
+    block::BlockId, canvas::Canvas, error::Result, moves::AppliedMove, painting::Painting,
+};
+
+use super::{
+    cuts::{Candidates, Cut, CutSource, EdgeProfile, State},
+    Solver,
+};
+
+/// Keeps the `width` best partial solutions and expands all of them with every
+/// cut (plus auto-coloring of the new blocks) each round
+#[derive(Clone)]
+pub struct Beam {
+    pub width: usize,
+    pub step: u32,
+    /// How many of the strongest target edges per block to cut along, the grid when zero
+    pub edges: usize,
+}
+
+/// How many rounds without a new best solution to tolerate before giving up
+const PATIENCE: usize = 2;
+
+impl Solver for Beam {
+    fn name(&self) -> &'static str {
+        if self.edges > 0 {
+            "beam_edges"
+        } else if self.width >= 32 {
+            "beam_wide"
+        } else {
+            "beam"
+        }
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let source = CutSource::new(&profile, self.step, self.edges);
+        let mut candidates = Candidates::new(painting, &sums, source);
+
+        let initial = State::new(canvas, painting);
+        let mut best = initial.clone();
+        let mut beam = vec![initial];
+        let mut stale_rounds = 0;
+
+        while stale_rounds < PATIENCE {
+            let mut expansions = vec![];
+            for (state_idx, state) in beam.iter_mut().enumerate() {
+                let budget = state.budget();
+                if budget <= 0.0 {
+                    continue;
+                }
+                let total = state.total();
+                let blocks: Vec<BlockId> =
+                    state.canvas.blocks_iter().map(|b| b.id.clone()).collect();
+                for id in blocks {
+                    for c in candidates
+                        .of_block(&mut state.canvas, &id)?
+                        .iter()
+                        .filter(|c| (c.cut_cost.0 as f64) < budget)
+                        .take(self.width)
+                    {
+                        expansions.push(Expansion {
+                            state_idx,
+                            block_id: id.clone(),
+                            cut: c.cut,
+                            total: total + c.gain,
+                        });
+                    }
+                }
+            }
+            if expansions.is_empty() {
+                break;
+            }
+
+            expansions.sort_by(|a, b| a.total.total_cmp(&b.total));
+            // independent cuts applied in a different order end up at the same total
+            let mut seen = HashSet::new();
+            let mut next_beam = vec![];
+            for e in expansions {
+                if next_beam.len() >= self.width {
+                    break;
+                }
+                if !seen.insert((e.total * 1000.0).round() as i64) {
+                    continue;
+                }
+                let mut state = beam[e.state_idx].clone();
+                state.apply_cut(e.block_id, e.cut, painting, &sums)?;
+                next_beam.push(state);
+            }
+
+            if next_beam[0].total() < best.total() {
+                best = next_beam[0].clone();
+                stale_rounds = 0;
+            } else {
+                stale_rounds += 1;
+            }
+            beam = next_beam;
+        }
+
+        *canvas = best.canvas;
+        Ok(best.moves)
+    }
+}
+
+struct Expansion {
+    state_idx: usize,
+    block_id: BlockId,
+    cut: Cut,
+    total: f64,
+}
@@ -1,5 +1,6 @@
 use crate::{
     canvas::Canvas,
+    error::{Result, ResultExt},
     moves::{AppliedMove, Move},
     painting::Painting,
 };
@@ -14,12 +15,16 @@ pub struct Chain {
     processors: Vec<Box<dyn Processor>>,
 }
 
-fn load_init_moves(canvas: &mut Canvas, moves: &Vec<Move>) -> Vec<AppliedMove> {
+fn load_init_moves(canvas: &mut Canvas, moves: &Vec<Move>) -> Result<Vec<AppliedMove>> {
     let mut res = vec![];
     for mov in moves {
-        res.push(mov.clone().apply(canvas).unwrap());
+        res.push(
+            mov.clone()
+                .apply(canvas)
+                .context("couldn't apply an input move")?,
+        );
     }
-    res
+    Ok(res)
 }
 
 impl Solver for Chain {
@@ -27,19 +32,20 @@ impl Solver for Chain {
         &self.name
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove> {
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
         let mut applied_moves = match &self.input_moves {
-            Some(moves) => load_init_moves(canvas, moves),
+            Some(moves) => load_init_moves(canvas, moves)?,
             None => vec![],
         };
 
         for s in &self.solvers {
-            applied_moves.extend(s.solve_core(canvas, painting));
+            applied_moves.extend(s.solve_core(canvas, painting).context(s.name())?);
         }
         for p in &self.processors {
-            p.process(&mut applied_moves, canvas, painting);
+            p.process(&mut applied_moves, canvas, painting)
+                .context(p.name())?;
         }
-        applied_moves
+        Ok(applied_moves)
     }
 }
 
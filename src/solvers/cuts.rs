@@ -0,0 +1,447 @@
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, HashMap},
+};
+
+use crate::{
+    block::{Block, BlockData, BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Cost, Move, MoveType, Orientation, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::auto_color;
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Cut {
+    Line(Orientation, u32),
+    Point(u32, u32),
+}
+
+impl Cut {
+    pub fn move_type(&self) -> MoveType {
+        match self {
+            Cut::Line(..) => MoveType::LineCut,
+            Cut::Point(..) => MoveType::PointCut,
+        }
+    }
+
+    pub fn to_move(self, block_id: BlockId) -> Move {
+        match self {
+            Cut::Line(orientation, offset) => Move::LineCut(block_id, orientation, offset),
+            Cut::Point(x, y) => Move::PointCut(block_id, x, y),
+        }
+    }
+
+    /// The rectangles of the blocks this cut creates, if it lies strictly inside `r`
+    pub fn child_rects(&self, r: &Rect) -> Option<Vec<Rect>> {
+        let inside_x = |x: u32| r.x() < x && x < r.top_right.x;
+        let inside_y = |y: u32| r.y() < y && y < r.top_right.y;
+        match *self {
+            Cut::Line(Orientation::Vertical, x) if inside_x(x) => {
+                let (left, right) = r.vertical_cut(x);
+                Some(vec![left, right])
+            }
+            Cut::Line(Orientation::Horizontal, y) if inside_y(y) => {
+                let (bottom, top) = r.horizontal_cut(y);
+                Some(vec![bottom, top])
+            }
+            Cut::Point(x, y) if inside_x(x) && inside_y(y) => {
+                let (bl, br, tr, tl) = r.cross_cut(x, y);
+                Some(vec![bl, br, tr, tl])
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Line cuts every `step` pixels, and point cuts every `xstep` pixels on both axes
+fn grid_cuts(r: &Rect, step: u32, xstep: u32) -> Vec<Cut> {
+    let (step, xstep) = (step.max(1), xstep.max(1));
+    let mut cuts = vec![];
+    for x in (step..r.width()).step_by(step as usize) {
+        cuts.push(Cut::Line(Orientation::Vertical, r.x() + x));
+    }
+    for y in (step..r.height()).step_by(step as usize) {
+        cuts.push(Cut::Line(Orientation::Horizontal, r.y() + y));
+    }
+    for x in (xstep..r.width()).step_by(xstep as usize) {
+        for y in (xstep..r.height()).step_by(xstep as usize) {
+            cuts.push(Cut::Point(r.x() + x, r.y() + y));
+        }
+    }
+    cuts
+}
+
+/// Line cuts at about `lines` evenly spread offsets per axis, and point cuts on a grid
+/// of about `points` offsets per axis
+fn spread_cuts(r: &Rect, lines: u32, points: u32) -> Vec<Cut> {
+    let offsets = |from: u32, len: u32, candidates: u32| {
+        let step = (len / candidates).max(1) as usize;
+        (from + 1..from + len).step_by(step)
+    };
+
+    let mut cuts = vec![];
+    for x in offsets(r.x(), r.width(), lines) {
+        cuts.push(Cut::Line(Orientation::Vertical, x));
+    }
+    for y in offsets(r.y(), r.height(), lines) {
+        cuts.push(Cut::Line(Orientation::Horizontal, y));
+    }
+    for x in offsets(r.x(), r.width(), points) {
+        for y in offsets(r.y(), r.height(), points) {
+            cuts.push(Cut::Point(x, y));
+        }
+    }
+    cuts
+}
+
+/// Contrast profiles of the target, to cut where the image changes rather than on
+/// a fixed grid
+pub(super) struct EdgeProfile {
+    width: u32,
+    height: u32,
+    /// `columns[x * (height + 1) + y]`: the contrast between the columns `x - 1`
+    /// and `x`, summed over the rows below `y`
+    columns: Vec<u64>,
+    /// `rows[y * (width + 1) + x]`: the same between the rows `y - 1` and `y`
+    rows: Vec<u64>,
+}
+
+/// The difference between two pixels, summed over the RGBA channels
+pub(super) fn contrast(a: Color, b: Color) -> u64 {
+    let (a, b) = ([a.r(), a.g(), a.b(), a.a()], [b.r(), b.g(), b.b(), b.a()]);
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(b) as u64).sum()
+}
+
+impl EdgeProfile {
+    pub fn new(painting: &Painting) -> Self {
+        let (width, height) = (painting.width(), painting.height());
+        let mut columns = vec![0; ((width + 1) * (height + 1)) as usize];
+        let mut rows = vec![0; ((width + 1) * (height + 1)) as usize];
+        for x in 1..width {
+            let base = (x * (height + 1)) as usize;
+            for y in 0..height {
+                let c = contrast(painting.get_color(x, y), painting.get_color(x - 1, y));
+                columns[base + y as usize + 1] = columns[base + y as usize] + c;
+            }
+        }
+        for y in 1..height {
+            let base = (y * (width + 1)) as usize;
+            for x in 0..width {
+                let c = contrast(painting.get_color(x, y), painting.get_color(x, y - 1));
+                rows[base + x as usize + 1] = rows[base + x as usize] + c;
+            }
+        }
+        EdgeProfile {
+            width,
+            height,
+            columns,
+            rows,
+        }
+    }
+
+    /// The contrast across a cut of the block at `offset`
+    pub fn strength(&self, r: &Rect, orientation: Orientation, offset: u32) -> u64 {
+        let (profile, base, from, to) = match orientation {
+            Orientation::Vertical => (
+                &self.columns,
+                offset * (self.height + 1),
+                r.y(),
+                r.top_right.y,
+            ),
+            Orientation::Horizontal => {
+                (&self.rows, offset * (self.width + 1), r.x(), r.top_right.x)
+            }
+        };
+        profile[(base + to) as usize] - profile[(base + from) as usize]
+    }
+
+    /// The contrast across every line strictly inside the block that has any
+    pub fn lines(&self, r: &Rect, orientation: Orientation) -> HashMap<u32, u64> {
+        let (from, to) = match orientation {
+            Orientation::Vertical => (r.x(), r.top_right.x),
+            Orientation::Horizontal => (r.y(), r.top_right.y),
+        };
+        (from + 1..to)
+            .map(|k| (k, self.strength(r, orientation, k)))
+            .filter(|&(_, s)| s > 0)
+            .collect()
+    }
+
+    /// At most `count` offsets inside the block where the contrast across it peaks,
+    /// strongest first. Blurry edges span several pixels, only their peak counts.
+    pub fn line_offsets(&self, r: &Rect, orientation: Orientation, count: usize) -> Vec<u32> {
+        let (from, to) = match orientation {
+            Orientation::Vertical => (r.x(), r.top_right.x),
+            Orientation::Horizontal => (r.y(), r.top_right.y),
+        };
+        let strengths: Vec<u64> = (from + 1..to)
+            .map(|k| self.strength(r, orientation, k))
+            .collect();
+        let mut peaks: Vec<(u64, u32)> = vec![];
+        for (i, &s) in strengths.iter().enumerate() {
+            let left = i.checked_sub(1).map_or(0, |i| strengths[i]);
+            let right = strengths.get(i + 1).copied().unwrap_or_default();
+            if s > 0 && s >= left && s > right {
+                peaks.push((s, from + 1 + i as u32));
+            }
+        }
+        peaks.sort_by_key(|&(s, k)| (Reverse(s), k));
+        peaks.into_iter().take(count).map(|(_, k)| k).collect()
+    }
+
+    /// The block borders along `orientation` and the `count` strongest edges between
+    /// them, sorted, for the solvers cutting on a fixed set of coordinates
+    pub fn coords(&self, r: &Rect, orientation: Orientation, count: usize) -> Vec<u32> {
+        let mut coords = self.line_offsets(r, orientation, count);
+        coords.extend(match orientation {
+            Orientation::Vertical => [r.x(), r.top_right.x],
+            Orientation::Horizontal => [r.y(), r.top_right.y],
+        });
+        coords.sort();
+        coords
+    }
+
+    /// The `count` strongest line cuts of each orientation, and the point cuts where
+    /// the strongest half of them cross
+    pub fn cuts(&self, r: &Rect, count: usize) -> Vec<Cut> {
+        let xs = self.line_offsets(r, Orientation::Vertical, count);
+        let ys = self.line_offsets(r, Orientation::Horizontal, count);
+        let mut cuts: Vec<Cut> = xs
+            .iter()
+            .map(|&x| Cut::Line(Orientation::Vertical, x))
+            .chain(ys.iter().map(|&y| Cut::Line(Orientation::Horizontal, y)))
+            .collect();
+        let half = count.div_ceil(2);
+        for &x in xs.iter().take(half) {
+            for &y in ys.iter().take(half) {
+                cuts.push(Cut::Point(x, y));
+            }
+        }
+        cuts
+    }
+}
+
+/// Where the cuts of a block come from
+#[derive(Clone, Copy)]
+pub(super) enum CutSource<'a> {
+    /// Line cuts every `step` pixels and point cuts every `xstep`, see `grid_cuts`
+    Grid(u32, u32),
+    /// The given number of offsets per axis for line and point cuts, see `spread_cuts`
+    Spread(u32, u32),
+    /// The given number of the strongest edges of the target, see `EdgeProfile::cuts`
+    Edges(&'a EdgeProfile, usize),
+}
+
+impl<'a> CutSource<'a> {
+    /// The edges of the profile, or the grid with point cuts four times coarser than
+    /// the line cuts when no edges are asked for
+    pub fn new(profile: &'a EdgeProfile, step: u32, edges: usize) -> Self {
+        if edges > 0 {
+            CutSource::Edges(profile, edges)
+        } else {
+            CutSource::Grid(step, step * 4)
+        }
+    }
+
+    pub fn cuts(&self, r: &Rect) -> Vec<Cut> {
+        match *self {
+            CutSource::Grid(step, xstep) => grid_cuts(r, step, xstep),
+            CutSource::Spread(lines, points) => spread_cuts(r, lines, points),
+            CutSource::Edges(profile, count) => profile.cuts(r, count),
+        }
+    }
+}
+
+pub(super) struct Candidate {
+    pub cut: Cut,
+    pub cut_cost: Cost,
+    /// Change of the total score, including the auto-coloring
+    pub gain: f64,
+}
+
+/// Evaluates the cuts of every block, followed by auto-coloring of the new blocks
+pub(super) struct Candidates<'a> {
+    painting: &'a Painting,
+    sums: &'a ColorSums,
+    source: CutSource<'a>,
+    /// The outcome of a cut only depends on the block geometry and contents
+    cache: HashMap<(Rect, Option<Color>), Vec<Candidate>>,
+}
+
+impl<'a> Candidates<'a> {
+    pub fn new(painting: &'a Painting, sums: &'a ColorSums, source: CutSource<'a>) -> Self {
+        Candidates {
+            painting,
+            sums,
+            source,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Every cut of the block, sorted by how much it changes the total score
+    pub fn of_block(&mut self, canvas: &mut Canvas, block_id: &BlockId) -> Result<&[Candidate]> {
+        let b = canvas.get_move_block(block_id)?;
+        let key = (b.r, simple_color(b));
+        match self.cache.entry(key) {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => {
+                let candidates = evaluate(canvas, block_id, self.painting, self.sums, self.source)?;
+                Ok(e.insert(candidates))
+            }
+        }
+    }
+}
+
+fn evaluate(
+    canvas: &mut Canvas,
+    block_id: &BlockId,
+    painting: &Painting,
+    sums: &ColorSums,
+    source: CutSource,
+) -> Result<Vec<Candidate>> {
+    let b = canvas.get_move_block(block_id)?;
+    let r = b.r;
+    let before = painting.calculate_score_block(b);
+
+    let mut candidates = vec![];
+    for cut in source.cuts(&r) {
+        let am = cut.to_move(block_id.clone()).apply(canvas)?;
+        let cut_cost = am.cost;
+        let mut cost = am.cost;
+        let mut after = 0.0;
+        for child_id in am.created_blocks() {
+            let child = canvas.get_move_block(&child_id)?;
+            let (score, color) = auto_color(canvas, painting, sums, child);
+            after += score;
+            if let Some((_, color_cost)) = color {
+                cost += color_cost;
+            }
+        }
+        am.undo(canvas);
+        candidates.push(Candidate {
+            cut,
+            cut_cost,
+            gain: (after - before) * SIMILARITY_FACTOR + cost.0 as f64,
+        });
+    }
+    candidates.sort_by(|a, b| a.gain.total_cmp(&b.gain));
+    Ok(candidates)
+}
+
+/// A partial program along with the canvas it produces
+#[derive(Clone)]
+pub(super) struct State {
+    pub canvas: Canvas,
+    pub moves: Vec<AppliedMove>,
+    pub move_cost: Cost,
+    /// Unscaled sum of the block scores
+    pub similarity: f64,
+}
+
+impl State {
+    pub fn new(canvas: &Canvas, painting: &Painting) -> Self {
+        State {
+            similarity: canvas
+                .blocks_iter()
+                .map(|b| painting.calculate_score_block(b))
+                .sum(),
+            canvas: canvas.clone(),
+            moves: vec![],
+            move_cost: Cost(0),
+        }
+    }
+
+    pub fn total(&self) -> f64 {
+        self.similarity * SIMILARITY_FACTOR + self.move_cost.0 as f64
+    }
+
+    /// Same pruning as `Simple`: a cut costing more than the remaining score can't pay off
+    pub fn budget(&self) -> f64 {
+        self.similarity * SIMILARITY_FACTOR - self.move_cost.0 as f64
+    }
+
+    /// Applies the cut, then colors the new blocks when that pays off
+    pub fn apply_cut(
+        &mut self,
+        block_id: BlockId,
+        cut: Cut,
+        painting: &Painting,
+        sums: &ColorSums,
+    ) -> Result<()> {
+        let before = painting.calculate_score_block(self.canvas.get_move_block(&block_id)?);
+        let am = cut.to_move(block_id).apply(&mut self.canvas)?;
+        self.move_cost += am.cost;
+        let children = am.created_blocks();
+        self.moves.push(am);
+
+        let mut after = 0.0;
+        for child_id in children {
+            let child = self.canvas.get_move_block(&child_id)?;
+            let (score, color) = auto_color(&self.canvas, painting, sums, child);
+            after += score;
+            if let Some((c, _)) = color {
+                let am = Move::Color(child_id, c).apply(&mut self.canvas)?;
+                self.move_cost += am.cost;
+                self.moves.push(am);
+            }
+        }
+        self.similarity += after - before;
+        Ok(())
+    }
+
+    /// Colors the block, as a layer that its children inherit once it gets cut
+    pub fn apply_color(&mut self, block_id: BlockId, c: Color, painting: &Painting) -> Result<()> {
+        let before = painting.calculate_score_block(self.canvas.get_move_block(&block_id)?);
+        let am = Move::Color(block_id.clone(), c).apply(&mut self.canvas)?;
+        self.move_cost += am.cost;
+        self.moves.push(am);
+        let after = painting.calculate_score_block(self.canvas.get_move_block(&block_id)?);
+        self.similarity += after - before;
+        Ok(())
+    }
+}
+
+pub(super) fn simple_color(b: &Block) -> Option<Color> {
+    match b.data {
+        BlockData::Simple(c) => Some(c),
+        BlockData::Complex(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edge_cuts_follow_the_target() {
+        // a dark left part, a one pixel wide blurry border, and a bright right part
+        let (width, height) = (30, 20);
+        let mut data = vec![];
+        for _ in 0..height {
+            for x in 0..width {
+                let v = match x {
+                    0..=11 => 0,
+                    12 => 100,
+                    _ => 255,
+                };
+                data.push(Color::new(v, v, v, 255));
+            }
+        }
+        let profile = EdgeProfile::new(&Painting::new(width, height, data));
+        let whole = Rect::from_coords([0, 0, width, height]);
+        assert_eq!(
+            profile.line_offsets(&whole, Orientation::Vertical, 4),
+            vec![13]
+        );
+        assert!(profile
+            .line_offsets(&whole, Orientation::Horizontal, 4)
+            .is_empty());
+        // only the part of the edge inside the block counts
+        let left = Rect::from_coords([0, 0, 12, height]);
+        assert!(profile.cuts(&left, 4).is_empty());
+    }
+}
@@ -1,18 +1,33 @@
 use crate::{
-    block::{BlockId, Point},
+    block::{BlockId, Rect},
     canvas::Canvas,
-    color::Color,
-    moves::{AppliedMove, Cost, Move},
-    painting::Painting,
+    error::{Error, Result},
+    moves::{AppliedMove, Cost, Move, MoveType, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
 };
 
-use super::{Solution, Solver};
+use super::{
+    cuts::{Cut, CutSource, EdgeProfile},
+    optimal_colors::optimize_colors,
+    split::{best_split, split_block, split_source, undo_since},
+    Solution, Solver,
+};
 
+/// Tries every cut cost limit from `min_move_cost` to `max_move_cost`, keeping the best
 #[derive(Clone)]
-pub struct DivideConquerSolver {}
+pub struct DivideConquerSolver {
+    pub min_move_cost: u64,
+    pub max_move_cost: u64,
+    pub move_cost_step: u64,
+    /// When not zero, the blocks get cut along this many of their strongest edges
+    /// instead of evenly spread offsets
+    pub edges: usize,
+}
 
-struct Params {
+struct Params<'a> {
     max_move_cost: u64,
+    sums: &'a ColorSums,
+    source: CutSource<'a>,
 }
 
 impl Solver for DivideConquerSolver {
@@ -20,48 +35,64 @@ impl Solver for DivideConquerSolver {
         "divide_conquer"
     }
 
-    fn solve(&self, canvas: &mut Canvas, painting: &Painting) -> Solution {
-        let mut applied_moves = vec![];
-        let mut cost = Cost(u64::MAX);
-        let mut result = None;
-        let mut max_move_cost = 100;
+    fn solve(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Solution> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let mut best: Option<(Cost, Canvas, Vec<AppliedMove>)> = None;
+        let mut max_move_cost = self.min_move_cost;
 
-        while max_move_cost <= 1000 {
+        while max_move_cost <= self.max_move_cost {
             let mut canvas = canvas.clone();
             let mut iteration_moves = vec![];
-            let mut iteration_cost = Cost(0);
             self.solve_block(
-                &Params { max_move_cost },
+                &Params {
+                    max_move_cost,
+                    sums: &sums,
+                    source: split_source(&profile, self.edges),
+                },
                 &mut canvas,
                 painting,
                 &BlockId::initial_root(),
                 &mut iteration_moves,
-                &mut iteration_cost,
-            );
-            if iteration_cost.0 < cost.0 {
-                applied_moves = iteration_moves;
-                cost = iteration_cost;
-                result = Some(canvas.render());
+            )?;
+            let iteration_cost = iteration_moves.iter().map(|am| am.cost).sum::<Cost>();
+            let total = painting.calculate_score_canvas(&canvas) + iteration_cost;
+            if best
+                .as_ref()
+                .is_none_or(|(best_total, ..)| total.0 < best_total.0)
+            {
+                best = Some((total, canvas, iteration_moves));
             }
-            max_move_cost += 100;
+            max_move_cost += self.move_cost_step.max(1);
         }
+        let (_, best_canvas, mut applied_moves) = best.ok_or_else(|| {
+            Error::Solver("divide_conquer: the move cost range is empty".to_owned())
+        })?;
+        *canvas = best_canvas;
+        optimize_colors(&mut applied_moves, canvas, painting)?;
+        let mut cost = Cost(0);
         let mut moves = vec![];
         for am in applied_moves {
+            cost += am.cost;
             moves.push(am.mov);
         }
-        Solution {
-            result: result.unwrap(),
+        Ok(Solution {
+            result: canvas.render(),
             moves,
             cost,
-        }
+        })
     }
 
-    fn solve_core(&self, _canvas: &mut Canvas, _painting: &Painting) -> Vec<AppliedMove> {
-        panic!("API users must call solve() instead")
+    fn solve_core(&self, _canvas: &mut Canvas, _painting: &Painting) -> Result<Vec<AppliedMove>> {
+        Err(Error::Solver(
+            "divide_conquer can't be chained, API users must call solve() instead".to_owned(),
+        ))
     }
 }
 
 impl DivideConquerSolver {
+    /// Colors the block or splits it and recurses, whichever ends up cheaper.
+    /// Returns the total cost of the block: moves plus the similarity penalty.
     fn solve_block(
         &self,
         params: &Params,
@@ -69,53 +100,65 @@ impl DivideConquerSolver {
         painting: &Painting,
         id: &BlockId,
         moves: &mut Vec<AppliedMove>,
-        cost: &mut Cost,
-    ) {
+    ) -> Result<f64> {
         const SMALLEST_SIZE: u32 = 4;
-
-        let block = canvas
-            .get_block(id)
-            .unwrap_or_else(|| panic!("DivideConquerSolver: Can't get block {id}"));
-
-        let r = &block.r;
-        // This is recalculated twice, essentially :(
-        let counts = painting.count_colors(r);
-
-        if counts.len() == 1 || r.width() < SMALLEST_SIZE || r.height() < SMALLEST_SIZE {
-            let best_color = Color::find_average(&counts);
-            let mov = Move::Color(id.to_owned(), best_color);
-            let applied_move = mov.apply(canvas).unwrap();
-            *cost += applied_move.cost;
-            moves.push(applied_move);
-            return;
-        }
-
-        let Point { x, y } = r.center();
-
-        // TODO: assess move cost before performing it
-        let cut = Move::PointCut(id.to_owned(), x, y);
-        let applied_move = cut.apply(canvas).unwrap();
-        if applied_move.cost.0 > params.max_move_cost {
-            applied_move.undo(canvas);
-            let best_color = Color::find_average(&counts);
-            let mov = Move::Color(id.to_owned(), best_color);
-            let applied_move = mov.apply(canvas).unwrap();
-            *cost += applied_move.cost;
-            moves.push(applied_move);
-            return;
+        let sums = params.sums;
+
+        let r = canvas.get_move_block(id)?.r;
+        let best_color = sums.average(&r);
+        let color_total = canvas.compute_cost(MoveType::Color, r.area()).0 as f64
+            + painting.calculate_score_rect(&r, best_color) * SIMILARITY_FACTOR;
+
+        let split = if sums.squared_error(&r) == 0.0
+            || r.width() < SMALLEST_SIZE
+            || r.height() < SMALLEST_SIZE
+        {
+            None
+        } else {
+            best_average_split(canvas, sums, params.source, &r)
+        };
+
+        // every child gets colored at least, so this is a lower bound of the split cost
+        let worth_splitting = |split: &Cut| {
+            let cut_cost = canvas.compute_cost(split.move_type(), r.area());
+            let children_lower_bound = split
+                .child_rects(&r)
+                .unwrap_or_default()
+                .iter()
+                .map(|c| canvas.compute_cost(MoveType::Color, c.area()).0 as f64)
+                .sum::<f64>();
+            cut_cost.0 <= params.max_move_cost
+                && (cut_cost.0 as f64) + children_lower_bound < color_total
+        };
+
+        if let Some(split) = split.filter(worth_splitting) {
+            let mark = moves.len();
+            let split_total = split_block(canvas, id, split, moves, |canvas, child, moves| {
+                self.solve_block(params, canvas, painting, child, moves)
+            })?;
+            if split_total < color_total {
+                return Ok(split_total);
+            }
+            undo_since(canvas, moves, mark);
         }
-        *cost += applied_move.cost;
-
-        moves.push(applied_move);
 
-        let id0 = canvas.hit_test(x, y);
-        let id1 = canvas.hit_test(x - 1, y);
-        let id2 = canvas.hit_test(x, y - 1);
-        let id3 = canvas.hit_test(x - 1, y - 1);
-
-        self.solve_block(params, canvas, painting, &id0, moves, cost);
-        self.solve_block(params, canvas, painting, &id1, moves, cost);
-        self.solve_block(params, canvas, painting, &id2, moves, cost);
-        self.solve_block(params, canvas, painting, &id3, moves, cost);
+        let mov = Move::Color(id.to_owned(), best_color);
+        moves.push(mov.apply(canvas)?);
+        Ok(color_total)
     }
 }
+
+/// The split with the lowest estimated cost when all children get their average color.
+/// The similarity of a child is estimated from its color variance as `sqrt(n * sse)`,
+/// which bounds the sum of the pixel distances from above.
+fn best_average_split(
+    canvas: &Canvas,
+    sums: &ColorSums,
+    source: CutSource,
+    r: &Rect,
+) -> Option<Cut> {
+    best_split(canvas, r, source, |c| {
+        canvas.compute_cost(MoveType::Color, c.area()).0 as f64
+            + (c.area() as f64 * sums.squared_error(c)).sqrt() * SIMILARITY_FACTOR
+    })
+}
@@ -0,0 +1,268 @@
+use crate::{
+    block::{BlockData, BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Move, MoveType, Orientation, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::{cuts::EdgeProfile, Solver};
+
+/// Optimal guillotine partition of every initial block, restricted to a grid
+/// of `grid` x `grid` candidate cut positions per block
+#[derive(Clone)]
+pub struct Dp {
+    pub grid: u32,
+    /// When not zero, the candidate cut positions are this many of the strongest
+    /// edges of the block per axis instead of the grid
+    pub edges: usize,
+}
+
+impl Solver for Dp {
+    fn name(&self) -> &'static str {
+        if self.grid <= 8 {
+            "dp_coarse"
+        } else {
+            "dp"
+        }
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = (self.edges > 0).then(|| EdgeProfile::new(painting));
+        let mut roots: Vec<(BlockId, Rect)> =
+            canvas.blocks_iter().map(|b| (b.id.clone(), b.r)).collect();
+        roots.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+
+        let mut applied_moves = vec![];
+        for (id, r) in roots {
+            let (xs, ys) = match &profile {
+                Some(profile) => (
+                    profile.coords(&r, Orientation::Vertical, self.edges),
+                    profile.coords(&r, Orientation::Horizontal, self.edges),
+                ),
+                None => (
+                    candidate_coords(r.x(), r.top_right.x, self.grid),
+                    candidate_coords(r.y(), r.top_right.y, self.grid),
+                ),
+            };
+            partition(canvas, painting, &sums, &id, xs, ys, &mut applied_moves)?;
+        }
+        Ok(applied_moves)
+    }
+}
+
+/// Optimal guillotine partition of the block, cutting only at the given sorted
+/// coordinates, which must start and end at the block borders
+pub(super) fn partition(
+    canvas: &mut Canvas,
+    painting: &Painting,
+    sums: &ColorSums,
+    id: &BlockId,
+    xs: Vec<u32>,
+    ys: Vec<u32>,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<()> {
+    let background = match canvas.get_move_block(id)?.data {
+        BlockData::Simple(c) => Some(c),
+        BlockData::Complex(_) => None,
+    };
+    let mut table = Table::new(painting, sums, xs, ys, background);
+    let (x1, y1) = (table.xs.len() - 1, table.ys.len() - 1);
+    table.solve(canvas, 0, x1, 0, y1);
+    table.emit(canvas, id, (0, x1, 0, y1), applied_moves)
+}
+
+#[derive(Clone, Copy)]
+enum Choice {
+    /// Leave the block as it is in the initial canvas
+    Keep,
+    Color(Color),
+    /// Index into the candidate coordinates of the cut axis
+    LineCut(Orientation, usize),
+    PointCut(usize, usize),
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    cost: f64,
+    choice: Choice,
+}
+
+/// Memoized best choices, keyed by rectangles spanning candidate coordinate indices
+struct Table<'a> {
+    painting: &'a Painting,
+    sums: &'a ColorSums,
+    background: Option<Color>,
+    xs: Vec<u32>,
+    ys: Vec<u32>,
+    memo: Vec<Option<Node>>,
+}
+
+fn candidate_coords(from: u32, to: u32, grid: u32) -> Vec<u32> {
+    let grid = grid.max(1);
+    let mut coords: Vec<u32> = (0..=grid).map(|k| from + (to - from) * k / grid).collect();
+    coords.dedup();
+    coords
+}
+
+fn move_cost(canvas: &Canvas, mov: MoveType, area: u32) -> f64 {
+    canvas.compute_cost(mov, area).0 as f64
+}
+
+impl<'a> Table<'a> {
+    fn new(
+        painting: &'a Painting,
+        sums: &'a ColorSums,
+        xs: Vec<u32>,
+        ys: Vec<u32>,
+        background: Option<Color>,
+    ) -> Self {
+        let size = xs.len() * xs.len() * ys.len() * ys.len();
+        Table {
+            painting,
+            sums,
+            background,
+            xs,
+            ys,
+            memo: vec![None; size],
+        }
+    }
+
+    fn index(&self, x0: usize, x1: usize, y0: usize, y1: usize) -> usize {
+        let (nx, ny) = (self.xs.len(), self.ys.len());
+        ((x0 * nx + x1) * ny + y0) * ny + y1
+    }
+
+    fn rect(&self, x0: usize, x1: usize, y0: usize, y1: usize) -> Rect {
+        Rect::from_coords([self.xs[x0], self.ys[y0], self.xs[x1], self.ys[y1]])
+    }
+
+    fn similarity(&self, r: &Rect, c: Color) -> f64 {
+        self.painting.calculate_score_rect(r, c) * SIMILARITY_FACTOR
+    }
+
+    fn solve(&mut self, canvas: &Canvas, x0: usize, x1: usize, y0: usize, y1: usize) -> f64 {
+        let idx = self.index(x0, x1, y0, y1);
+        if let Some(node) = self.memo[idx] {
+            return node.cost;
+        }
+
+        let r = self.rect(x0, x1, y0, y1);
+        let area = r.area();
+
+        let color = self.sums.average(&r);
+        let mut best = Node {
+            cost: move_cost(canvas, MoveType::Color, area) + self.similarity(&r, color),
+            choice: Choice::Color(color),
+        };
+        if let Some(background) = self.background {
+            let cost = self.similarity(&r, background);
+            if cost <= best.cost {
+                best = Node {
+                    cost,
+                    choice: Choice::Keep,
+                };
+            }
+        }
+
+        // children costs are non-negative, so a cut can only win if the cut itself is cheaper
+        let line_cost = move_cost(canvas, MoveType::LineCut, area);
+        if line_cost < best.cost {
+            for x in x0 + 1..x1 {
+                let cost = line_cost + self.solve(canvas, x0, x, y0, y1);
+                if cost >= best.cost {
+                    continue;
+                }
+                let cost = cost + self.solve(canvas, x, x1, y0, y1);
+                if cost < best.cost {
+                    best = Node {
+                        cost,
+                        choice: Choice::LineCut(Orientation::Vertical, x),
+                    };
+                }
+            }
+            for y in y0 + 1..y1 {
+                let cost = line_cost + self.solve(canvas, x0, x1, y0, y);
+                if cost >= best.cost {
+                    continue;
+                }
+                let cost = cost + self.solve(canvas, x0, x1, y, y1);
+                if cost < best.cost {
+                    best = Node {
+                        cost,
+                        choice: Choice::LineCut(Orientation::Horizontal, y),
+                    };
+                }
+            }
+        }
+
+        let point_cost = move_cost(canvas, MoveType::PointCut, area);
+        if point_cost < best.cost {
+            for x in x0 + 1..x1 {
+                for y in y0 + 1..y1 {
+                    let mut cost = point_cost;
+                    for (cx0, cx1, cy0, cy1) in [
+                        (x0, x, y0, y),
+                        (x, x1, y0, y),
+                        (x, x1, y, y1),
+                        (x0, x, y, y1),
+                    ] {
+                        cost += self.solve(canvas, cx0, cx1, cy0, cy1);
+                        if cost >= best.cost {
+                            break;
+                        }
+                    }
+                    if cost < best.cost {
+                        best = Node {
+                            cost,
+                            choice: Choice::PointCut(x, y),
+                        };
+                    }
+                }
+            }
+        }
+
+        self.memo[idx] = Some(best);
+        best.cost
+    }
+
+    fn emit(
+        &self,
+        canvas: &mut Canvas,
+        id: &BlockId,
+        (x0, x1, y0, y1): (usize, usize, usize, usize),
+        applied_moves: &mut Vec<AppliedMove>,
+    ) -> Result<()> {
+        let node =
+            self.memo[self.index(x0, x1, y0, y1)].expect("the chosen rectangles are always solved");
+        match node.choice {
+            Choice::Keep => {}
+            Choice::Color(c) => {
+                applied_moves.push(Move::Color(id.clone(), c).apply(canvas)?);
+            }
+            Choice::LineCut(Orientation::Vertical, x) => {
+                let mov = Move::LineCut(id.clone(), Orientation::Vertical, self.xs[x]);
+                applied_moves.push(mov.apply(canvas)?);
+                self.emit(canvas, &id.new_child("0"), (x0, x, y0, y1), applied_moves)?;
+                self.emit(canvas, &id.new_child("1"), (x, x1, y0, y1), applied_moves)?;
+            }
+            Choice::LineCut(Orientation::Horizontal, y) => {
+                let mov = Move::LineCut(id.clone(), Orientation::Horizontal, self.ys[y]);
+                applied_moves.push(mov.apply(canvas)?);
+                self.emit(canvas, &id.new_child("0"), (x0, x1, y0, y), applied_moves)?;
+                self.emit(canvas, &id.new_child("1"), (x0, x1, y, y1), applied_moves)?;
+            }
+            Choice::PointCut(x, y) => {
+                let mov = Move::PointCut(id.clone(), self.xs[x], self.ys[y]);
+                applied_moves.push(mov.apply(canvas)?);
+                self.emit(canvas, &id.new_child("0"), (x0, x, y0, y), applied_moves)?;
+                self.emit(canvas, &id.new_child("1"), (x, x1, y0, y), applied_moves)?;
+                self.emit(canvas, &id.new_child("2"), (x, x1, y, y1), applied_moves)?;
+                self.emit(canvas, &id.new_child("3"), (x0, x, y, y1), applied_moves)?;
+            }
+        }
+        Ok(())
+    }
+}
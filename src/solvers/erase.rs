@@ -1,10 +1,12 @@
 use crate::{
+    block::{Point, Rect},
     canvas::Canvas,
-    moves::{AppliedMove, Move},
+    error::Result,
+    moves::AppliedMove,
     painting::Painting,
 };
 
-use super::Solver;
+use super::{merge_plan::merge_region, Solver};
 
 #[derive(Clone)]
 pub struct Erase;
@@ -14,34 +16,13 @@ impl Solver for Erase {
         "erase"
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, _painting: &Painting) -> Vec<AppliedMove> {
-        let mut applied_moves = vec![];
-
-        // Detect block size
-        let size = canvas.blocks_iter().next().unwrap().r.width();
-        let count = canvas.width / size;
-
-        // Hit-test and merge all blocks into one
-        for x_i in 0..count {
-            for y_i in 1..count {
-                let b0_id = canvas.hit_test(x_i * size, y_i * size - 1);
-                let b1_id = canvas.hit_test(x_i * size, y_i * size);
-                let mov = Move::Merge(b0_id, b1_id);
-                let am = mov.apply(canvas).unwrap();
-                applied_moves.push(am);
-            }
-        }
-
-        for x_i in 1..count {
-            let b0_id = canvas.hit_test(x_i * size - 1, 0);
-            let b1_id = canvas.hit_test(x_i * size, 0);
-            let mov = Move::Merge(b0_id, b1_id);
-            let am = mov.apply(canvas).unwrap();
-            applied_moves.push(am);
-        }
+    fn solve_core(&self, canvas: &mut Canvas, _painting: &Painting) -> Result<Vec<AppliedMove>> {
+        // Merge all blocks into one
+        let whole = Rect::from_dimensions(Point::new(0, 0), canvas.width, canvas.height);
+        let applied_moves = merge_region(canvas, &whole)?;
 
         // If default coloring is needed, use +top_color
 
-        applied_moves
+        Ok(applied_moves)
     }
 }
@@ -0,0 +1,302 @@
+use rand::{rngs::ThreadRng, seq::SliceRandom, Rng};
+use rayon::prelude::*;
+
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Cost, Move, Orientation},
+    painting::{ColorSums, Painting},
+};
+
+use super::{
+    cuts::{Cut, CutSource, EdgeProfile},
+    Solver,
+};
+
+/// Evolves a population of cut trees, one tree per initial block
+#[derive(Clone)]
+pub struct Genetic {
+    pub population: usize,
+    pub generations: usize,
+    /// When not zero, random cuts go along one of this many of the strongest edges
+    /// of the block instead of anywhere inside it
+    pub edges: usize,
+}
+
+const TOURNAMENT_SIZE: usize = 3;
+const ELITE: usize = 2;
+const MUTATION_RATE: f64 = 0.5;
+const MAX_INITIAL_DEPTH: u32 = 4;
+const MAX_SHIFT: i64 = 20;
+const MAX_COLOR_SHIFT: i16 = 16;
+
+type Genome = Vec<Gene>;
+
+impl Solver for Genetic {
+    fn name(&self) -> &'static str {
+        "genetic"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let source = (self.edges > 0).then_some(CutSource::Edges(&profile, self.edges));
+        let mut roots: Vec<(BlockId, Rect)> =
+            canvas.blocks_iter().map(|b| (b.id.clone(), b.r)).collect();
+        roots.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+
+        let mut rng = rand::thread_rng();
+        // seed with the two trivial solutions so that we never end up worse than them
+        let mut population: Vec<Genome> = vec![
+            roots.iter().map(|_| Gene::Leaf(None)).collect(),
+            roots
+                .iter()
+                .map(|(_, r)| Gene::Leaf(Some(sums.average(r))))
+                .collect(),
+        ];
+        while population.len() < self.population.max(ELITE) {
+            population.push(
+                roots
+                    .iter()
+                    .map(|(_, r)| Gene::random(r, &sums, source, MAX_INITIAL_DEPTH, &mut rng))
+                    .collect(),
+            );
+        }
+
+        let mut scored = self.evaluate(population, &roots, canvas, painting)?;
+        for _ in 0..self.generations {
+            let mut next: Vec<Genome> = scored.iter().take(ELITE).map(|(g, _)| g.clone()).collect();
+            while next.len() < scored.len() {
+                let a = tournament(&scored, &mut rng);
+                let b = tournament(&scored, &mut rng);
+                for mut child in crossover(a, b, &roots, &mut rng) {
+                    if rng.gen_bool(MUTATION_RATE) {
+                        mutate(&mut child, &roots, &sums, source, &mut rng);
+                    }
+                    if next.len() < scored.len() {
+                        next.push(child);
+                    }
+                }
+            }
+            scored = self.evaluate(next, &roots, canvas, painting)?;
+        }
+
+        let mut applied_moves = vec![];
+        for (gene, (id, _)) in scored[0].0.iter().zip(&roots) {
+            gene.express(id.clone(), canvas, &mut applied_moves)?;
+        }
+        Ok(applied_moves)
+    }
+}
+
+impl Genetic {
+    /// Pairs every genome with its total score, best first
+    fn evaluate(
+        &self,
+        population: Vec<Genome>,
+        roots: &[(BlockId, Rect)],
+        canvas: &Canvas,
+        painting: &Painting,
+    ) -> Result<Vec<(Genome, Cost)>> {
+        let mut scored = population
+            .into_par_iter()
+            .map(|genome| {
+                let mut canvas = canvas.clone();
+                let mut applied_moves = vec![];
+                for (gene, (id, _)) in genome.iter().zip(roots) {
+                    gene.express(id.clone(), &mut canvas, &mut applied_moves)?;
+                }
+                let cost = applied_moves.iter().map(|am| am.cost).sum::<Cost>();
+                let total = painting.calculate_score_canvas(&canvas) + cost;
+                Ok((genome, total))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        scored.sort_by_key(|(_, total)| total.0);
+        Ok(scored)
+    }
+}
+
+/// One of the cuts of the source, or a cut with offsets strictly inside the block,
+/// if there are any
+fn random_cut(r: &Rect, source: Option<CutSource>, rng: &mut ThreadRng) -> Option<Cut> {
+    if let Some(source) = source {
+        return source.cuts(r).choose(rng).copied();
+    }
+    let x = (r.width() > 1).then(|| r.x() + rng.gen_range(1..r.width()));
+    let y = (r.height() > 1).then(|| r.y() + rng.gen_range(1..r.height()));
+    match (x, y) {
+        (None, None) => None,
+        (Some(x), None) => Some(Cut::Line(Orientation::Vertical, x)),
+        (None, Some(y)) => Some(Cut::Line(Orientation::Horizontal, y)),
+        (Some(x), Some(y)) => Some(match rng.gen_range(0..3) {
+            0 => Cut::Line(Orientation::Vertical, x),
+            1 => Cut::Line(Orientation::Horizontal, y),
+            _ => Cut::Point(x, y),
+        }),
+    }
+}
+
+fn shifted(cut: Cut, rng: &mut ThreadRng) -> Cut {
+    let mut shift = |v: u32| (v as i64 + rng.gen_range(-MAX_SHIFT..=MAX_SHIFT)).max(0) as u32;
+    match cut {
+        Cut::Line(orientation, offset) => Cut::Line(orientation, shift(offset)),
+        Cut::Point(x, y) => Cut::Point(shift(x), shift(y)),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Gene {
+    /// Optionally colored block
+    Leaf(Option<Color>),
+    /// Children are in block id order
+    Cut(Cut, Vec<Gene>),
+}
+
+impl Gene {
+    fn random(
+        r: &Rect,
+        sums: &ColorSums,
+        source: Option<CutSource>,
+        depth: u32,
+        rng: &mut ThreadRng,
+    ) -> Gene {
+        let cut = if depth > 0 && rng.gen_bool(0.7) {
+            random_cut(r, source, rng)
+        } else {
+            None
+        };
+        match cut.and_then(|cut| cut.child_rects(r).map(|rects| (cut, rects))) {
+            Some((cut, rects)) => Gene::Cut(
+                cut,
+                rects
+                    .iter()
+                    .map(|r| Gene::random(r, sums, source, depth - 1, rng))
+                    .collect(),
+            ),
+            None => Gene::Leaf(Some(sums.average(r))),
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Gene::Leaf(_) => 1,
+            Gene::Cut(_, children) => 1 + children.iter().map(|c| c.count()).sum::<usize>(),
+        }
+    }
+
+    /// The `n`-th node in pre-order along with its rectangle, or how many nodes are
+    /// still left to skip. Subtrees of cuts that don't fit their block are never
+    /// expressed and therefore skipped.
+    fn nth_mut(&mut self, r: Rect, n: usize) -> std::result::Result<(&mut Gene, Rect), usize> {
+        if n == 0 {
+            return Ok((self, r));
+        }
+        let mut n = n - 1;
+        if let Gene::Cut(cut, children) = self {
+            let rects = cut.child_rects(&r).unwrap_or_default();
+            for (child, r) in children.iter_mut().zip(rects) {
+                match child.nth_mut(r, n) {
+                    Ok(res) => return Ok(res),
+                    Err(left) => n = left,
+                }
+            }
+        }
+        Err(n)
+    }
+
+    /// Applies the tree to the block, ignoring the cuts that don't fit
+    fn express(
+        &self,
+        block_id: BlockId,
+        canvas: &mut Canvas,
+        applied_moves: &mut Vec<AppliedMove>,
+    ) -> Result<()> {
+        match self {
+            Gene::Leaf(None) => {}
+            Gene::Leaf(Some(c)) => {
+                applied_moves.push(Move::Color(block_id, *c).apply(canvas)?);
+            }
+            Gene::Cut(cut, children) => {
+                let r = canvas.get_move_block(&block_id)?.r;
+                if cut.child_rects(&r).is_none() {
+                    return Ok(());
+                }
+                applied_moves.push(cut.to_move(block_id.clone()).apply(canvas)?);
+                for (i, child) in children.iter().enumerate() {
+                    child.express(block_id.new_child(&i.to_string()), canvas, applied_moves)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn tournament<'a>(scored: &'a [(Genome, Cost)], rng: &mut ThreadRng) -> &'a Genome {
+    (0..TOURNAMENT_SIZE)
+        .map(|_| scored.choose(rng).unwrap())
+        .min_by_key(|(_, total)| total.0)
+        .map(|(genome, _)| genome)
+        .unwrap()
+}
+
+/// Exchanges a random subtree of `a` with a random subtree of `b` growing from the
+/// same initial block, giving two children
+fn crossover(
+    a: &Genome,
+    b: &Genome,
+    roots: &[(BlockId, Rect)],
+    rng: &mut ThreadRng,
+) -> [Genome; 2] {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    let i = rng.gen_range(0..a.len());
+    let (n, m) = (
+        rng.gen_range(0..a[i].count()),
+        rng.gen_range(0..b[i].count()),
+    );
+    if let (Ok((x, _)), Ok((y, _))) = (a[i].nth_mut(roots[i].1, n), b[i].nth_mut(roots[i].1, m)) {
+        std::mem::swap(x, y);
+    }
+    [a, b]
+}
+
+fn mutate(
+    genome: &mut Genome,
+    roots: &[(BlockId, Rect)],
+    sums: &ColorSums,
+    source: Option<CutSource>,
+    rng: &mut ThreadRng,
+) {
+    let i = rng.gen_range(0..genome.len());
+    let n = rng.gen_range(0..genome[i].count());
+    let (gene, r) = match genome[i].nth_mut(roots[i].1, n) {
+        Ok(res) => res,
+        Err(_) => return,
+    };
+    let replacement = match gene {
+        Gene::Leaf(color) => match rng.gen_range(0..4) {
+            0 => Gene::Leaf(Some(sums.average(&r))),
+            1 => Gene::Leaf(None),
+            2 => Gene::Leaf(Some(nudged(color.unwrap_or(sums.average(&r)), rng))),
+            _ => Gene::random(&r, sums, source, 1, rng),
+        },
+        Gene::Cut(cut, children) => {
+            if rng.gen_bool(0.8) {
+                Gene::Cut(shifted(*cut, rng), children.clone())
+            } else {
+                Gene::Leaf(Some(sums.average(&r)))
+            }
+        }
+    };
+    *gene = replacement;
+}
+
+/// The color with every channel moved by a random delta
+fn nudged(mut color: Color, rng: &mut ThreadRng) -> Color {
+    for ch in color.0.iter_mut() {
+        let delta = rng.gen_range(-MAX_COLOR_SHIFT..=MAX_COLOR_SHIFT);
+        *ch = (*ch as i16 + delta).clamp(0, 255) as u8;
+    }
+    color
+}
@@ -0,0 +1,185 @@
+use crate::{
+    block::{BlockData, BlockId, Point, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Cost, Move, MoveType, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::{
+    cuts::{Cut, CutSource, EdgeProfile},
+    merge_plan::merge_region,
+    split::{best_split, split_block, split_source, undo_since},
+    Solver,
+};
+
+/// Paints a block with its dominant color before cutting it, so that the children
+/// inherit that color and only the ones that differ get recolored. Coloring costs
+/// inversely to the block area, which makes painting large layers first cheap.
+#[derive(Clone)]
+pub struct Layers {
+    /// When not zero, the blocks get cut along this many of their strongest edges
+    /// instead of evenly spread offsets
+    pub edges: usize,
+}
+
+const SMALLEST_SIZE: u32 = 4;
+
+impl Solver for Layers {
+    fn name(&self) -> &'static str {
+        "layers"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let source = split_source(&profile, self.edges);
+        // merging all the initial blocks gives one large layer to start from
+        let merge_options = if canvas.blocks_count() > 1 {
+            vec![false, true]
+        } else {
+            vec![false]
+        };
+
+        let mut best: Option<(u64, Canvas, Vec<AppliedMove>)> = None;
+        for merge_first in merge_options {
+            let mut candidate = canvas.clone();
+            let mut moves = vec![];
+            if merge_first {
+                let whole = Rect::from_dimensions(Point::new(0, 0), canvas.width, canvas.height);
+                // the planner may find no merge order, then the blocks stay as they are
+                match merge_region(&mut candidate, &whole) {
+                    Ok(merges) => moves = merges,
+                    Err(_) => continue,
+                }
+            }
+            let mut roots: Vec<BlockId> = candidate.blocks_iter().map(|b| b.id.clone()).collect();
+            roots.sort_by(|a, b| a.0.cmp(&b.0));
+            for root in roots {
+                self.paint_block(&mut candidate, painting, &sums, source, &root, &mut moves)?;
+            }
+
+            let cost = moves.iter().map(|am| am.cost).sum::<Cost>();
+            let total = (painting.calculate_score_canvas(&candidate) + cost).0;
+            if best
+                .as_ref()
+                .is_none_or(|(best_total, ..)| total < *best_total)
+            {
+                best = Some((total, candidate, moves));
+            }
+        }
+
+        let (_, best_canvas, moves) = best.unwrap();
+        *canvas = best_canvas;
+        Ok(moves)
+    }
+}
+
+impl Layers {
+    /// Keeps or repaints the block, then refines it by cutting and recursing into the
+    /// children when that ends up cheaper. Returns the total cost of the block: moves
+    /// plus the similarity penalty.
+    fn paint_block(
+        &self,
+        canvas: &mut Canvas,
+        painting: &Painting,
+        sums: &ColorSums,
+        source: CutSource,
+        id: &BlockId,
+        moves: &mut Vec<AppliedMove>,
+    ) -> Result<f64> {
+        let b = canvas.get_move_block(id)?;
+        let r = b.r;
+        let keep_total = painting.calculate_score_block(b) * SIMILARITY_FACTOR;
+        let current = match b.data {
+            BlockData::Simple(c) => Some(c),
+            BlockData::Complex(_) => None,
+        };
+
+        let (color, score) = layer_color(painting, sums, &r);
+        let color_total =
+            canvas.compute_cost(MoveType::Color, r.area()).0 as f64 + score * SIMILARITY_FACTOR;
+        let (leaf_total, paint) = if color_total < keep_total {
+            (color_total, Some(color))
+        } else {
+            (keep_total, None)
+        };
+
+        let split = if leaf_total == 0.0 || r.width() < SMALLEST_SIZE || r.height() < SMALLEST_SIZE
+        {
+            None
+        } else {
+            // children may keep the inherited color for free, so only the moves made here
+            // bound the cost of the split from below
+            let inherited = paint.or(current);
+            best_layer_split(canvas, sums, source, &r, inherited).filter(|split| {
+                let cut_cost = canvas.compute_cost(split.move_type(), r.area());
+                (cut_cost.0 as f64) + paint_cost(canvas, &r, paint) < leaf_total
+            })
+        };
+
+        let mark = moves.len();
+        let mut total = 0.0;
+        if let Some(c) = paint {
+            let am = Move::Color(id.clone(), c).apply(canvas)?;
+            total += am.cost.0 as f64;
+            moves.push(am);
+        }
+        if let Some(split) = split {
+            let split_total = total
+                + split_block(canvas, id, split, moves, |canvas, child, moves| {
+                    self.paint_block(canvas, painting, sums, source, child, moves)
+                })?;
+            if split_total < leaf_total {
+                return Ok(split_total);
+            }
+            undo_since(canvas, moves, mark);
+            if let Some(c) = paint {
+                moves.push(Move::Color(id.clone(), c).apply(canvas)?);
+            }
+        }
+        Ok(leaf_total)
+    }
+}
+
+fn paint_cost(canvas: &Canvas, r: &Rect, paint: Option<Color>) -> f64 {
+    match paint {
+        Some(_) => canvas.compute_cost(MoveType::Color, r.area()).0 as f64,
+        None => 0.0,
+    }
+}
+
+/// The most common color or the average one, whichever matches the block better,
+/// along with its unscaled score
+pub(super) fn layer_color(painting: &Painting, sums: &ColorSums, r: &Rect) -> (Color, f64) {
+    [
+        Color::find_most_common(&painting.count_colors(r)),
+        sums.average(r),
+    ]
+    .into_iter()
+    .map(|c| (c, painting.calculate_score_rect(r, c)))
+    .min_by(|a, b| a.1.total_cmp(&b.1))
+    .unwrap()
+}
+
+/// The split with the lowest estimated cost. Every child either
+/// keeps the inherited color or gets its average one. The similarity of a child is
+/// estimated as `sqrt(n * squared distance)`, which bounds the sum of the pixel distances.
+fn best_layer_split(
+    canvas: &Canvas,
+    sums: &ColorSums,
+    source: CutSource,
+    r: &Rect,
+    inherited: Option<Color>,
+) -> Option<Cut> {
+    best_split(canvas, r, source, |c| {
+        let n = c.area() as f64;
+        let colored = canvas.compute_cost(MoveType::Color, c.area()).0 as f64
+            + (n * sums.squared_error(c)).sqrt() * SIMILARITY_FACTOR;
+        match inherited {
+            Some(p) => colored.min((n * sums.squared_distance(c, p)).sqrt() * SIMILARITY_FACTOR),
+            None => colored,
+        }
+    })
+}
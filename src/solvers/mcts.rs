@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, MoveType, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::{
+    cuts::{simple_color, Candidates, Cut, CutSource, EdgeProfile, State},
+    layers::layer_color,
+    Solver,
+};
+
+/// Monte Carlo tree search over programs. Every node is a partial program, its
+/// actions are the most promising cuts of its blocks (each followed by auto-coloring)
+/// and colorings of whole blocks, which the children of later cuts inherit. New nodes
+/// are valued by a greedy rollout applying the best improving cut until there is
+/// none left.
+#[derive(Clone)]
+pub struct Mcts {
+    pub step: u32,
+    /// When not zero, the cuts follow this many of the strongest edges of every block
+    pub edges: usize,
+    /// How many of the best cuts of every node get explored
+    pub width: usize,
+    pub budget: Duration,
+}
+
+const EXPLORATION: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+struct Node {
+    state: State,
+    /// The untried actions, most promising last
+    actions: Vec<Action>,
+    children: Vec<usize>,
+    parent: Option<usize>,
+    visits: u32,
+    /// Sum of the rollout totals
+    value: f64,
+    /// Whether the whole subtree has been expanded
+    done: bool,
+}
+
+impl Solver for Mcts {
+    fn name(&self) -> &'static str {
+        "mcts"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let start = Instant::now();
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let source = CutSource::new(&profile, self.step, self.edges);
+        let mut candidates = Candidates::new(painting, &sums, source);
+        let mut layers = Layers {
+            painting,
+            sums: &sums,
+            colors: HashMap::new(),
+        };
+
+        let mut root = State::new(canvas, painting);
+        let actions = self.actions(&mut root, &mut candidates, &mut layers)?;
+        let mut best = root.clone();
+        // the range of the rollout totals, to normalize the exploitation term
+        let mut bounds = (best.total(), best.total());
+        let mut nodes = vec![Node {
+            done: actions.is_empty(),
+            state: root,
+            actions,
+            children: vec![],
+            parent: None,
+            visits: 0,
+            value: 0.0,
+        }];
+
+        while start.elapsed() < self.budget && !nodes[0].done {
+            let mut idx = 0;
+            while nodes[idx].actions.is_empty() {
+                idx = select(&nodes, idx, bounds);
+            }
+
+            let action = nodes[idx].actions.pop().unwrap();
+            let mut state = nodes[idx].state.clone();
+            match action {
+                Action::Cut(block_id, cut) => state.apply_cut(block_id, cut, painting, &sums)?,
+                Action::Color(block_id, c) => state.apply_color(block_id, c, painting)?,
+            }
+            let actions = self.actions(&mut state, &mut candidates, &mut layers)?;
+            nodes.push(Node {
+                done: actions.is_empty(),
+                state,
+                actions,
+                children: vec![],
+                parent: Some(idx),
+                visits: 0,
+                value: 0.0,
+            });
+            let child = nodes.len() - 1;
+            nodes[idx].children.push(child);
+
+            let result = rollout(nodes[child].state.clone(), &mut candidates, painting, &sums)?;
+            let total = result.total();
+            bounds = (bounds.0.min(total), bounds.1.max(total));
+            if total < best.total() {
+                best = result;
+            }
+
+            let mut cur = Some(child);
+            while let Some(i) = cur {
+                let node = &nodes[i];
+                let done = node.actions.is_empty() && node.children.iter().all(|&c| nodes[c].done);
+                let node = &mut nodes[i];
+                node.visits += 1;
+                node.value += total;
+                node.done = done;
+                cur = node.parent;
+            }
+        }
+
+        *canvas = best.canvas;
+        Ok(best.moves)
+    }
+}
+
+enum Action {
+    Cut(BlockId, Cut),
+    Color(BlockId, Color),
+}
+
+/// The layer color of every block shape seen, along with its unscaled score
+struct Layers<'a> {
+    painting: &'a Painting,
+    sums: &'a ColorSums,
+    colors: HashMap<Rect, (Color, f64)>,
+}
+
+impl Layers<'_> {
+    fn of(&mut self, r: &Rect) -> (Color, f64) {
+        *self
+            .colors
+            .entry(*r)
+            .or_insert_with(|| layer_color(self.painting, self.sums, r))
+    }
+}
+
+impl Mcts {
+    /// The best cuts and colorings over all blocks of the state, most promising last
+    fn actions(
+        &self,
+        state: &mut State,
+        candidates: &mut Candidates,
+        layers: &mut Layers,
+    ) -> Result<Vec<Action>> {
+        let budget = state.budget();
+        let blocks: Vec<BlockId> = state.canvas.blocks_iter().map(|b| b.id.clone()).collect();
+        let mut actions = vec![];
+        for id in blocks {
+            for c in candidates
+                .of_block(&mut state.canvas, &id)?
+                .iter()
+                .filter(|c| (c.cut_cost.0 as f64) < budget)
+                .take(self.width)
+            {
+                actions.push((c.gain, Action::Cut(id.clone(), c.cut)));
+            }
+
+            let b = state.canvas.get_move_block(&id)?;
+            let (c, score) = layers.of(&b.r);
+            let color_cost = state.canvas.compute_cost(MoveType::Color, b.area()).0 as f64;
+            if simple_color(b) != Some(c) && color_cost < budget {
+                let keep = layers.painting.calculate_score_block(b);
+                let gain = (score - keep) * SIMILARITY_FACTOR + color_cost;
+                actions.push((gain, Action::Color(id, c)));
+            }
+        }
+        actions.sort_by(|a, b| a.0.total_cmp(&b.0));
+        actions.truncate(self.width);
+        Ok(actions
+            .into_iter()
+            .rev()
+            .map(|(_, action)| action)
+            .collect())
+    }
+}
+
+/// UCB1 over the children that still have something to expand. Lower totals are
+/// better, so the exploitation term is 1 for the best rollout seen and 0 for the worst.
+fn select(nodes: &[Node], idx: usize, (min, max): (f64, f64)) -> usize {
+    let parent_visits = (nodes[idx].visits as f64).max(1.0);
+    let range = (max - min).max(1.0);
+    let ucb = |&c: &usize| {
+        let node = &nodes[c];
+        let visits = node.visits as f64;
+        let exploitation = (max - node.value / visits) / range;
+        exploitation + EXPLORATION * (parent_visits.ln() / visits).sqrt()
+    };
+    nodes[idx]
+        .children
+        .iter()
+        .filter(|&&c| !nodes[c].done)
+        .max_by(|a, b| ucb(a).total_cmp(&ucb(b)))
+        .copied()
+        .expect("a node that isn't done has children left to explore")
+}
+
+/// Keeps applying the cut that improves the total the most
+fn rollout(
+    mut state: State,
+    candidates: &mut Candidates,
+    painting: &Painting,
+    sums: &ColorSums,
+) -> Result<State> {
+    loop {
+        let budget = state.budget();
+        let blocks: Vec<BlockId> = state.canvas.blocks_iter().map(|b| b.id.clone()).collect();
+        let mut best: Option<(f64, BlockId, Cut)> = None;
+        for id in blocks {
+            let found = candidates
+                .of_block(&mut state.canvas, &id)?
+                .iter()
+                .find(|c| (c.cut_cost.0 as f64) < budget);
+            if let Some(c) = found {
+                if c.gain < 0.0 && best.as_ref().is_none_or(|(gain, ..)| c.gain < *gain) {
+                    best = Some((c.gain, id, c.cut));
+                }
+            }
+        }
+        match best {
+            Some((_, id, cut)) => state.apply_cut(id, cut, painting, sums)?,
+            None => return Ok(state),
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    error::{Error, Result},
+    moves::{AppliedMove, Move, MoveType},
+};
+
+/// How many layouts the exact search may go through before leaving the order to
+/// the greedy one
+const MAX_EXACT_LAYOUTS: usize = 20_000;
+/// How many merges the greedy search may try before giving up
+const MAX_SEARCHED_MERGES: usize = 10_000;
+
+/// Merges every block inside `region` into a single block, whatever the layout.
+///
+/// The order with the lowest total cost comes from a search over every layout the
+/// merges can go through, when there are at most `MAX_EXACT_LAYOUTS` of them.
+/// Larger layouts, like the grids of the source images, get a greedy order instead:
+/// a merge costs inversely to the area of the larger block, so the cheapest merge
+/// available is usually the one growing the largest block, which builds long strips
+/// and joins them. Some orders lead to layouts where no two blocks share a whole
+/// edge, like a pinwheel, so the greedy search backtracks to the next cheapest merge
+/// when it gets stuck, and fails after `MAX_SEARCHED_MERGES` merges. On failure the
+/// canvas is left unchanged.
+pub(crate) fn merge_region(canvas: &mut Canvas, region: &Rect) -> Result<Vec<AppliedMove>> {
+    let mut blocks = vec![];
+    for b in canvas.blocks_iter() {
+        let r = b.r;
+        let overlaps = r.x() < region.top_right.x
+            && region.x() < r.top_right.x
+            && r.y() < region.top_right.y
+            && region.y() < r.top_right.y;
+        let inside = region.x() <= r.x()
+            && r.top_right.x <= region.top_right.x
+            && region.y() <= r.y()
+            && r.top_right.y <= region.top_right.y;
+        if inside {
+            blocks.push((b.id.clone(), r));
+        } else if overlaps {
+            return Err(Error::Solver(format!(
+                "merge: block [{}] crosses the region border",
+                b.id
+            )));
+        }
+    }
+    if blocks.is_empty() {
+        return Err(Error::Solver("merge: the region has no blocks".to_owned()));
+    }
+
+    let mut applied_moves = vec![];
+    let mut budget = MAX_SEARCHED_MERGES;
+    let rects: Vec<Rect> = blocks.iter().map(|(_, r)| *r).collect();
+    let merged = match cheapest_order(canvas, rects) {
+        Some(order) => apply_order(canvas, blocks, order, &mut applied_moves)?,
+        None => search(canvas, blocks, &mut applied_moves, &mut budget)?,
+    };
+    if merged {
+        Ok(applied_moves)
+    } else {
+        Err(Error::Solver(
+            "merge: the blocks can't be merged without cutting them".to_owned(),
+        ))
+    }
+}
+
+/// A layout the merges go through, with the cheapest way to reach it
+struct Layout {
+    rects: Vec<Rect>,
+    cost: u64,
+    /// The layout of the previous level it comes from, and the rectangles merged
+    from: Option<(usize, Rect, Rect)>,
+}
+
+/// The merges of the cheapest order, as the pairs of rectangles they join. `None`
+/// when there are too many layouts to go through, `Some` of an empty order when
+/// the blocks can't be merged at all.
+fn cheapest_order(canvas: &Canvas, rects: Vec<Rect>) -> Option<Vec<(Rect, Rect)>> {
+    // every merge leaves one block less, so the layouts come in levels
+    let mut levels = vec![vec![Layout {
+        rects: sorted(rects),
+        cost: 0,
+        from: None,
+    }]];
+    let mut count = 1;
+    while levels.last().unwrap()[0].rects.len() > 1 {
+        let mut next: Vec<Layout> = vec![];
+        let mut index: HashMap<Vec<Rect>, usize> = HashMap::new();
+        for (from, layout) in levels.last().unwrap().iter().enumerate() {
+            for (a, b) in merges(canvas, &layout.rects) {
+                let (ra, rb) = (layout.rects[a], layout.rects[b]);
+                let area = ra.area().max(rb.area());
+                let cost = layout.cost + canvas.compute_cost(MoveType::Merge, area).0;
+                let mut rects: Vec<Rect> = layout
+                    .rects
+                    .iter()
+                    .filter(|r| **r != ra && **r != rb)
+                    .copied()
+                    .collect();
+                rects.push(Rect::from_coords([
+                    ra.x().min(rb.x()),
+                    ra.y().min(rb.y()),
+                    ra.top_right.x.max(rb.top_right.x),
+                    ra.top_right.y.max(rb.top_right.y),
+                ]));
+                let merged = Layout {
+                    rects: sorted(rects),
+                    cost,
+                    from: Some((from, ra, rb)),
+                };
+                match index.get(&merged.rects) {
+                    Some(&i) if next[i].cost <= cost => {}
+                    Some(&i) => next[i] = merged,
+                    None => {
+                        index.insert(merged.rects.clone(), next.len());
+                        next.push(merged);
+                        count += 1;
+                        if count > MAX_EXACT_LAYOUTS {
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+        if next.is_empty() {
+            return Some(vec![]);
+        }
+        levels.push(next);
+    }
+
+    let mut order = vec![];
+    let mut i = 0;
+    for level in levels.iter().rev() {
+        if let Some((from, ra, rb)) = level[i].from {
+            order.push((ra, rb));
+            i = from;
+        }
+    }
+    order.reverse();
+    Some(order)
+}
+
+fn sorted(mut rects: Vec<Rect>) -> Vec<Rect> {
+    rects.sort_by_key(|r| (r.y(), r.x()));
+    rects
+}
+
+/// Applies the merges of the order, returning whether they merged all the blocks
+fn apply_order(
+    canvas: &mut Canvas,
+    blocks: Vec<(BlockId, Rect)>,
+    order: Vec<(Rect, Rect)>,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<bool> {
+    if order.len() + 1 != blocks.len() {
+        return Ok(false);
+    }
+    let mut ids: HashMap<Rect, BlockId> = blocks.into_iter().map(|(id, r)| (r, id)).collect();
+    for (ra, rb) in order {
+        let (a, b) = (ids.remove(&ra).unwrap(), ids.remove(&rb).unwrap());
+        let am = Move::Merge(a, b).apply(canvas)?;
+        let merged_id = am.created_blocks().remove(0);
+        ids.insert(canvas.get_move_block(&merged_id)?.r, merged_id);
+        applied_moves.push(am);
+    }
+    Ok(true)
+}
+
+/// Depth-first search over the merges, cheapest first. Returns whether the blocks
+/// ended up merged, the moves of a dead end being undone.
+fn search(
+    canvas: &mut Canvas,
+    blocks: Vec<(BlockId, Rect)>,
+    applied_moves: &mut Vec<AppliedMove>,
+    budget: &mut usize,
+) -> Result<bool> {
+    if blocks.len() == 1 {
+        return Ok(true);
+    }
+    let rects: Vec<Rect> = blocks.iter().map(|(_, r)| *r).collect();
+    for (a, b) in merges(canvas, &rects) {
+        if *budget == 0 {
+            return Ok(false);
+        }
+        *budget -= 1;
+
+        let am = Move::Merge(blocks[a].0.clone(), blocks[b].0.clone()).apply(canvas)?;
+        let merged_id = am.created_blocks().remove(0);
+        let merged_r = canvas.get_move_block(&merged_id)?.r;
+        applied_moves.push(am);
+
+        let mut rest = blocks.clone();
+        rest.swap_remove(a.max(b));
+        rest.swap_remove(a.min(b));
+        rest.push((merged_id, merged_r));
+        if search(canvas, rest, applied_moves, budget)? {
+            return Ok(true);
+        }
+        applied_moves.pop().unwrap().undo(canvas);
+    }
+    Ok(false)
+}
+
+/// Indices of the pairs of blocks that can be merged, cheapest first, ties going to
+/// the bottom left
+fn merges(canvas: &Canvas, rects: &[Rect]) -> Vec<(usize, usize)> {
+    // blocks by their left and bottom edges, so that neighbours sharing a whole edge
+    // can be looked up from the right and top edges
+    let mut by_left = HashMap::new();
+    let mut by_bottom = HashMap::new();
+    for (i, r) in rects.iter().enumerate() {
+        by_left.insert((r.x(), r.y(), r.top_right.y), i);
+        by_bottom.insert((r.y(), r.x(), r.top_right.x), i);
+    }
+
+    let mut pairs: Vec<(usize, usize)> = rects
+        .iter()
+        .enumerate()
+        .flat_map(|(a, r)| {
+            let right = by_left.get(&(r.top_right.x, r.y(), r.top_right.y));
+            let top = by_bottom.get(&(r.top_right.y, r.x(), r.top_right.x));
+            right.into_iter().chain(top).map(move |&b| (a, b))
+        })
+        .collect();
+    pairs.sort_by_key(|&(a, b)| {
+        let r = rects[a];
+        let area = r.area().max(rects[b].area());
+        (canvas.compute_cost(MoveType::Merge, area).0, r.y(), r.x())
+    });
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{Block, Point},
+        color::Color,
+    };
+
+    /// Columns of different widths, split into rows of different heights
+    fn make_uneven_canvas() -> Canvas {
+        let xs = [0, 5, 20, 32];
+        let ys = [0, 10, 13, 32];
+        let mut blocks = vec![];
+        for (i, x) in xs.windows(2).enumerate() {
+            for (j, y) in ys.windows(2).enumerate() {
+                blocks.push(Block::new_simple(
+                    BlockId::new_root((i * 3 + j) as u32),
+                    Rect::new(Point::new(x[0], y[0]), Point::new(x[1], y[1])),
+                    Color::new(255, 255, 255, 255),
+                ));
+            }
+        }
+        Canvas::from_blocks(32, 32, 9, 0, blocks.into_iter(), false)
+    }
+
+    #[test]
+    fn merges_uneven_grid() -> Result<()> {
+        let mut canvas = make_uneven_canvas();
+        let region = Rect::new(Point::new(0, 0), Point::new(32, 32));
+        let applied_moves = merge_region(&mut canvas, &region)?;
+        assert_eq!(applied_moves.len(), 8);
+        assert_eq!(canvas.blocks_count(), 1);
+        assert_eq!(canvas.blocks_iter().next().unwrap().r, region);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_a_cheaper_order_than_greedy() -> Result<()> {
+        let cost = |moves: &[AppliedMove]| moves.iter().map(|am| am.cost.0).sum::<u64>();
+        let mut canvas = make_uneven_canvas();
+        let blocks = canvas.blocks_iter().map(|b| (b.id.clone(), b.r)).collect();
+        let (mut greedy, mut budget) = (vec![], MAX_SEARCHED_MERGES);
+        assert!(search(&mut canvas, blocks, &mut greedy, &mut budget)?);
+
+        let mut canvas = make_uneven_canvas();
+        let region = Rect::new(Point::new(0, 0), Point::new(32, 32));
+        let exact = merge_region(&mut canvas, &region)?;
+        assert!(cost(&exact) < cost(&greedy));
+        Ok(())
+    }
+
+    #[test]
+    fn merges_only_the_region() -> Result<()> {
+        let mut canvas = make_uneven_canvas();
+        let region = Rect::new(Point::new(5, 0), Point::new(32, 13));
+        merge_region(&mut canvas, &region)?;
+        assert_eq!(canvas.blocks_count(), 6);
+        assert!(canvas.blocks_iter().any(|b| b.r == region));
+
+        let crossing = Rect::new(Point::new(0, 0), Point::new(10, 10));
+        assert!(merge_region(&mut canvas, &crossing).is_err());
+        Ok(())
+    }
+
+    /// A pinwheel around a center block, with its bottom arm split in two: merging
+    /// the two halves back is the cheapest merge but leaves no merge possible
+    #[test]
+    fn backtracks_out_of_a_pinwheel() -> Result<()> {
+        let rects = [
+            ((0, 0), (4, 16)),
+            ((4, 0), (28, 16)),
+            ((28, 0), (32, 20)),
+            ((4, 20), (32, 32)),
+            ((0, 16), (4, 32)),
+            ((4, 16), (28, 20)),
+        ];
+        let blocks = rects.iter().enumerate().map(|(i, ((x0, y0), (x1, y1)))| {
+            Block::new_simple(
+                BlockId::new_root(i as u32),
+                Rect::new(Point::new(*x0, *y0), Point::new(*x1, *y1)),
+                Color::new(255, 255, 255, 255),
+            )
+        });
+        let canvas = Canvas::from_blocks(32, 32, rects.len() as u32, 0, blocks, false);
+        let region = Rect::new(Point::new(0, 0), Point::new(32, 32));
+        let mut exact = canvas.clone();
+        let applied_moves = merge_region(&mut exact, &region)?;
+        assert_eq!(applied_moves.len(), rects.len() - 1);
+        assert_eq!(exact.blocks_count(), 1);
+
+        // the greedy order for layouts too large for the exact search
+        let mut greedy = canvas.clone();
+        let blocks = greedy.blocks_iter().map(|b| (b.id.clone(), b.r)).collect();
+        let (mut applied_moves, mut budget) = (vec![], MAX_SEARCHED_MERGES);
+        assert!(search(
+            &mut greedy,
+            blocks,
+            &mut applied_moves,
+            &mut budget
+        )?);
+        assert_eq!(greedy.blocks_count(), 1);
+        Ok(())
+    }
+}
@@ -1,23 +1,45 @@
 mod annealing;
+mod beam;
 mod chain;
+mod cuts;
 mod divide_conquer;
+mod dp;
 mod erase;
+mod genetic;
+mod layers;
+mod mcts;
+mod merge_plan;
+mod multires;
 mod no_op;
+mod optimal_colors;
+mod permute;
+mod preset;
 mod processors;
+mod repetition;
+mod segment;
 mod simple;
+mod source_reuse;
+mod split;
+mod strips;
 mod swapper;
+mod tempering;
+mod tiles;
 mod top_color;
 
 use std::path::PathBuf;
 
 use dyn_clone::DynClone;
+use rand::{rngs::StdRng, SeedableRng};
 
 use crate::{
+    block::Block,
     canvas::Canvas,
+    color::Color,
     dto::SolvedSolutionDto,
+    error::{Error, Result, ResultExt},
     helpers::os_str_to_str,
-    moves::{AppliedMove, Cost, Move},
-    painting::Painting,
+    moves::{AppliedMove, Cost, Move, MoveType, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
     program,
 };
 
@@ -30,11 +52,12 @@ pub struct Problem {
 }
 
 impl Problem {
-    pub fn load(problem_path: &PathBuf) -> std::io::Result<Self> {
+    pub fn load(problem_path: &PathBuf) -> Result<Self> {
         let id = os_str_to_str(problem_path.file_stem());
-        let reference_painting = Painting::load(problem_path);
+        let reference_painting = Painting::load(problem_path)?;
         let initial_canvas =
-            Canvas::try_create(problem_path.with_extension("json"), &reference_painting)?;
+            Canvas::try_create(problem_path.with_extension("json"), &reference_painting)
+                .with_context(|| format!("problem {id}"))?;
         Ok(Problem {
             id,
             reference_painting,
@@ -50,18 +73,18 @@ pub struct Solution {
 }
 
 impl Solution {
-    pub fn load(dir: &PathBuf, problem: &Problem) -> std::io::Result<(Self, SolvedSolutionDto)> {
+    pub fn load(dir: &PathBuf, problem: &Problem) -> Result<(Self, SolvedSolutionDto)> {
         let problem_base = dir.join(&problem.id);
         let isl_path = problem_base.with_extension("txt");
         let img_path = problem_base.with_extension("png");
         let meta_path = problem_base.with_file_name(format!("{}_meta.json", problem.id));
 
         let moves = crate::parser::parse_moves_from_file(&isl_path)?;
-        let result = Painting::load(&img_path);
+        let result = Painting::load(&img_path)?;
 
-        let current_best_json: String = std::fs::read_to_string(meta_path)?.into();
-        let metadata: SolvedSolutionDto =
-            serde_json::from_str(&current_best_json).expect("Deserialization error");
+        let current_best_json: String = std::fs::read_to_string(&meta_path)?.into();
+        let metadata: SolvedSolutionDto = serde_json::from_str(&current_best_json)
+            .with_context(|| format!("couldn't parse {}", meta_path.display()))?;
         let solution = Solution {
             result,
             moves,
@@ -75,14 +98,14 @@ impl Solution {
         solver_name: String,
         problem: &Problem,
         dir: &PathBuf,
-    ) -> std::io::Result<SolvedSolutionDto> {
+    ) -> Result<SolvedSolutionDto> {
         let problem_base = dir.join(&problem.id);
         let isl_path = problem_base.with_extension("txt");
         let img_path = problem_base.with_extension("png");
         let meta_path = problem_base.with_file_name(format!("{}_meta.json", problem.id));
 
         program::write_to_file(&isl_path, &self.moves)?;
-        self.result.write_to_file(&img_path);
+        self.result.write_to_file(&img_path)?;
 
         let score = problem.reference_painting.calculate_score(&self.result);
         let total = score + self.cost;
@@ -100,21 +123,24 @@ impl Solution {
 
 pub trait Solver: DynClone + Sync + Send {
     fn name(&self) -> &str;
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove>;
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>>;
 
-    fn solve(&self, canvas: &mut Canvas, painting: &Painting) -> Solution {
-        let applied_moves = self.solve_core(canvas, painting);
+    /// Solves with `solve_core`, then gives every color move the optimal color of
+    /// the pixels it paints
+    fn solve(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Solution> {
+        let mut applied_moves = self.solve_core(canvas, painting)?;
+        optimal_colors::optimize_colors(&mut applied_moves, canvas, painting)?;
         let mut cost = Cost(0);
         let mut moves = vec![];
         for am in applied_moves {
             cost += am.cost;
             moves.push(am.mov);
         }
-        Solution {
+        Ok(Solution {
             result: canvas.render(),
             moves,
             cost,
-        }
+        })
     }
 }
 
@@ -127,27 +153,55 @@ pub trait Processor: DynClone + Sync + Send {
         applied_moves: &mut Vec<AppliedMove>,
         canvas: &mut Canvas,
         painting: &Painting,
-    );
+    ) -> Result<()>;
 }
 
 dyn_clone::clone_trait_object!(Processor);
 
 pub const SOLVERS: &[&str] = &[
     "annealing",
+    "annealing_adaptive",
+    "annealing_linear",
     "annealing_s4",
     "avg_color",
+    "beam",
+    "beam_edges",
+    "beam_wide",
     "divide_conquer",
+    "dp",
+    "dp_coarse",
     "erase",
+    "genetic",
+    "layers",
+    "mcts",
+    "multires2_dp",
+    "multires2_simple",
+    "multires4_dp",
     "no_op",
+    "permute",
+    "segment",
     "simple",
+    "simple_edges",
     "simple_no_x",
     "simple_s1",
     "simple_no_x_s1",
+    "source_reuse",
+    "strips",
     "swapper",
+    "tempering",
+    "tiles",
     "top_color",
 ];
 
-pub fn create_solver(input_moves: Option<Vec<Move>>, solver_name: &str) -> Box<dyn Solver> {
+/// Solver and processor combinations worth running alongside the plain solvers
+pub const CHAINS: &[&str] = &[
+    "dp%recolor+trim",
+    "layers%trim",
+    "simple%shake+recolor",
+    "strips%recolor+trim",
+];
+
+pub fn create_solver(input_moves: Option<Vec<Move>>, solver_name: &str) -> Result<Box<dyn Solver>> {
     if solver_name.contains(&['+', '%']) {
         let (solver_name, processor_name) = if solver_name.contains('%') {
             let parts = solver_name.split_at(solver_name.find('%').unwrap());
@@ -158,57 +212,195 @@ pub fn create_solver(input_moves: Option<Vec<Move>>, solver_name: &str) -> Box<d
         let mut solvers = vec![];
         if !solver_name.is_empty() {
             for name in solver_name.split('+') {
-                solvers.push(create_individual_solver(name))
+                solvers.push(create_individual_solver(name)?)
             }
         }
         let mut processors = vec![];
         if let Some(processor_name) = processor_name {
             for name in processor_name.split('+') {
-                processors.push(create_processor(name))
+                processors.push(create_processor(name)?)
             }
         }
-        Box::new(Chain::new(input_moves, solvers, processors))
+        Ok(Box::new(Chain::new(input_moves, solvers, processors)))
     } else {
         create_individual_solver(solver_name)
     }
 }
 
-fn create_individual_solver(solver_name: &str) -> Box<dyn Solver> {
-    match solver_name {
-        "annealing" => Box::new(annealing::Annealing { step: 10 }),
-        "annealing_s4" => Box::new(annealing::Annealing { step: 4 }),
+fn create_individual_solver(spec: &str) -> Result<Box<dyn Solver>> {
+    // `multires<factor>_<solver spec>` runs the solver at a lower resolution first
+    if let Some((factor, inner)) = spec
+        .strip_prefix("multires")
+        .and_then(|rest| rest.split_once('_'))
+    {
+        let factor = factor
+            .parse()
+            .map_err(|_| Error::InvalidParameter(format!("bad multires factor: {factor}")))?;
+        let inner = create_individual_solver(inner)?;
+        return Ok(Box::new(multires::Multires::new(factor, inner)?));
+    }
+    let (solver_name, params) = spec.split_once('@').unwrap_or((spec, ""));
+    let mut params = preset::Params::parse(params)?;
+    let p = &mut params;
+    let annealing = |p: &mut preset::Params, step, schedule| -> Result<Box<dyn Solver>> {
+        Ok(Box::new(annealing::Annealing {
+            name: solver_name.to_owned(),
+            step: p.get("step", step)?,
+            edges: p.get("edges", 0)?,
+            schedule,
+            iterations: p.get("iterations", 5000)?,
+            restarts: p.get("restarts", 2)?,
+            reheats: p.get("reheats", 0)?,
+        }))
+    };
+    let simple =
+        |p: &mut preset::Params, allow_cross_cut, step, xstep, edges| -> Result<Box<dyn Solver>> {
+            Ok(Box::new(simple::Simple {
+                allow_cross_cut,
+                step: p.get("step", step)?,
+                xstep: p.get("xstep", xstep)?,
+                edges: p.get("edges", edges)?,
+            }))
+        };
+    let solver: Box<dyn Solver> = match solver_name {
+        "annealing" => annealing(p, 10, annealing::Schedule::Exponential)?,
+        "annealing_adaptive" => annealing(p, 10, annealing::Schedule::Adaptive)?,
+        "annealing_linear" => annealing(p, 10, annealing::Schedule::Linear)?,
+        "annealing_s4" => annealing(p, 4, annealing::Schedule::Exponential)?,
         "avg_color" => Box::new(top_color::TopColor { use_avg: true }),
-        "divide_conquer" => Box::new(divide_conquer::DivideConquerSolver {}),
+        "beam" => Box::new(beam::Beam {
+            width: p.get("width", 8)?,
+            step: p.get("step", 10)?,
+            edges: p.get("edges", 0)?,
+        }),
+        "beam_edges" => Box::new(beam::Beam {
+            width: p.get("width", 8)?,
+            step: p.get("step", 10)?,
+            edges: p.get("edges", 16)?,
+        }),
+        "beam_wide" => Box::new(beam::Beam {
+            width: p.get("width", 32)?,
+            step: p.get("step", 10)?,
+            edges: p.get("edges", 0)?,
+        }),
+        "divide_conquer" => Box::new(divide_conquer::DivideConquerSolver {
+            min_move_cost: p.get("min_move_cost", 100)?,
+            max_move_cost: p.get("max_move_cost", 1000)?,
+            move_cost_step: p.get("move_cost_step", 100)?,
+            edges: p.get("edges", 0)?,
+        }),
+        "dp" => Box::new(dp::Dp {
+            grid: p.get("grid", 16)?,
+            edges: p.get("edges", 0)?,
+        }),
+        "dp_coarse" => Box::new(dp::Dp {
+            grid: p.get("grid", 8)?,
+            edges: p.get("edges", 0)?,
+        }),
         "erase" => Box::new(erase::Erase {}),
-        "no_op" => Box::new(no_op::NoOp {}),
-        "simple" => Box::new(simple::Simple {
-            allow_cross_cut: true,
-            step_1: false,
+        "genetic" => Box::new(genetic::Genetic {
+            population: p.get("population", 64)?,
+            generations: p.get("generations", 300)?,
+            edges: p.get("edges", 0)?,
+        }),
+        "layers" => Box::new(layers::Layers {
+            edges: p.get("edges", 0)?,
         }),
-        "simple_no_x" => Box::new(simple::Simple {
-            allow_cross_cut: false,
-            step_1: false,
+        "mcts" => Box::new(mcts::Mcts {
+            step: p.get("step", 10)?,
+            width: p.get("width", 8)?,
+            edges: p.get("edges", 0)?,
+            budget: std::time::Duration::from_secs(p.get("budget", 10)?),
         }),
-        "simple_s1" => Box::new(simple::Simple {
-            allow_cross_cut: true,
-            step_1: true,
+        "no_op" => Box::new(no_op::NoOp {}),
+        "permute" => Box::new(permute::Permute {}),
+        "segment" => Box::new(segment::Segment {
+            colors: p.get("colors", 8)?,
+            max_coords: p.get("max_coords", 16)?,
+        }),
+        "simple" => simple(p, true, 2, 20, 0)?,
+        "simple_edges" => simple(p, true, 2, 20, 16)?,
+        "simple_no_x" => simple(p, false, 2, 20, 0)?,
+        "simple_s1" => simple(p, true, 1, 1, 0)?,
+        "simple_no_x_s1" => simple(p, false, 1, 1, 0)?,
+        "source_reuse" => Box::new(source_reuse::SourceReuse {
+            max_depth: p.get("max_depth", 3)?,
         }),
-        "simple_no_x_s1" => Box::new(simple::Simple {
-            allow_cross_cut: false,
-            step_1: true,
+        "strips" => Box::new(strips::Strips {
+            step: p.get("step", 4)?,
+            edges: p.get("edges", 0)?,
         }),
         "swapper" => Box::new(swapper::Swapper {}),
+        "tempering" => Box::new(tempering::Tempering {
+            chains: p.get("chains", 8)?,
+            iterations: p.get("iterations", 5000)?,
+            swap_interval: p.get("swap_interval", 50)?,
+            step: p.get("step", 10)?,
+            edges: p.get("edges", 0)?,
+            seed: p.get_optional("seed")?,
+        }),
+        "tiles" => Box::new(tiles::Tiles {
+            max_edges: p.get("max_edges", 8)?,
+        }),
         "top_color" => Box::new(top_color::TopColor { use_avg: false }),
-        n => panic!("Unknown solver `{}`", n),
+        n => return Err(Error::UnknownSolver(n.to_owned())),
+    };
+    params.finish(solver_name)?;
+    if params.is_empty() {
+        Ok(solver)
+    } else {
+        Ok(Box::new(preset::Preset {
+            name: spec.to_owned(),
+            solver,
+        }))
     }
 }
 
-fn create_processor(processor_name: &str) -> Box<dyn Processor> {
-    match processor_name {
+fn create_processor(spec: &str) -> Result<Box<dyn Processor>> {
+    let (processor_name, params) = spec.split_once('@').unwrap_or((spec, ""));
+    let mut params = preset::Params::parse(params)?;
+    let processor: Box<dyn Processor> = match processor_name {
         "recolor" => Box::new(processors::recolor::Recolor {}),
         "recolorv2" => Box::new(processors::recolorv2::Recolorv2 {}),
-        "shake" => Box::new(processors::shake::Shake {}),
+        "shake" => Box::new(processors::shake::Shake {
+            radius: params.get("radius", 8)?,
+        }),
         "trim" => Box::new(processors::trim::Trim {}),
-        n => panic!("Unknown procesor `{}`", n),
+        n => return Err(Error::UnknownProcessor(n.to_owned())),
+    };
+    params.finish(processor_name)?;
+    if params.is_empty() {
+        Ok(processor)
+    } else {
+        Ok(Box::new(preset::PresetProcessor {
+            name: spec.to_owned(),
+            processor,
+        }))
+    }
+}
+
+/// A reproducible RNG when a seed is given, otherwise a randomly seeded one
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// The block score after coloring it with its average color, if that pays off
+pub(crate) fn auto_color(
+    canvas: &Canvas,
+    painting: &Painting,
+    sums: &ColorSums,
+    b: &Block,
+) -> (f64, Option<(Color, Cost)>) {
+    let keep = painting.calculate_score_block(b);
+    let color = sums.average(&b.r);
+    let colored = painting.calculate_score_rect(&b.r, color);
+    let color_cost = canvas.compute_cost(MoveType::Color, b.area());
+    if colored * SIMILARITY_FACTOR + (color_cost.0 as f64) < keep * SIMILARITY_FACTOR {
+        (colored, Some((color, color_cost)))
+    } else {
+        (keep, None)
     }
 }
@@ -0,0 +1,83 @@
+use crate::{
+    canvas::Canvas,
+    error::{Error, Result},
+    moves::{AppliedMove, Move},
+    painting::Painting,
+};
+
+use super::{
+    processors::{recolor::Recolor, shake::Shake},
+    Processor, Solver,
+};
+
+/// Runs a solver on the target downscaled `factor` times, where it searches
+/// `factor²` times fewer pixels, then brings its program back up one resolution
+/// at a time: the cuts are scaled up, their offsets refined with `Shake`, and the
+/// colors picked again with `Recolor`.
+#[derive(Clone)]
+pub struct Multires {
+    pub factor: u32,
+    pub inner: Box<dyn Solver>,
+    pub name: String,
+}
+
+impl Multires {
+    pub fn new(factor: u32, inner: Box<dyn Solver>) -> Result<Self> {
+        if factor < 2 || !factor.is_power_of_two() {
+            return Err(Error::InvalidParameter(format!(
+                "multires: the factor must be a power of two, got {factor}"
+            )));
+        }
+        let name = format!("multires{factor}_{}", inner.name());
+        Ok(Multires {
+            factor,
+            inner,
+            name,
+        })
+    }
+}
+
+impl Solver for Multires {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let levels = self.factor.trailing_zeros() as usize;
+        // downscaled 2x, 4x, ..., `factor` times
+        let pyramid = painting.pyramid(levels);
+
+        let mut coarse = canvas.downscale(self.factor)?;
+        let mut applied_moves = self.inner.solve_core(&mut coarse, &pyramid[levels - 1])?;
+        for level in (1..levels).rev() {
+            let mut level_canvas = canvas.downscale(1 << level)?;
+            applied_moves = refine(&applied_moves, &mut level_canvas, &pyramid[level - 1])?;
+        }
+        refine(&applied_moves, canvas, painting)
+    }
+}
+
+/// Replays the moves found at half the resolution of the canvas and adjusts them
+fn refine(
+    coarse_moves: &[AppliedMove],
+    canvas: &mut Canvas,
+    painting: &Painting,
+) -> Result<Vec<AppliedMove>> {
+    let mut applied_moves = vec![];
+    for am in coarse_moves {
+        applied_moves.push(upscale(&am.mov).apply(canvas)?);
+    }
+    Recolor.process(&mut applied_moves, canvas, painting)?;
+    // the scaled up cuts are at most a pixel away from where they would be found
+    Shake { radius: 2 }.process(&mut applied_moves, canvas, painting)?;
+    Ok(applied_moves)
+}
+
+/// The same move on a canvas twice as large
+fn upscale(mov: &Move) -> Move {
+    match mov.clone() {
+        Move::LineCut(id, o, offset) => Move::LineCut(id, o, offset * 2),
+        Move::PointCut(id, x, y) => Move::PointCut(id, x * 2, y * 2),
+        mov => mov,
+    }
+}
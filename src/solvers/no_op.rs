@@ -1,4 +1,4 @@
-use crate::{canvas::Canvas, moves::AppliedMove, painting::Painting};
+use crate::{canvas::Canvas, error::Result, moves::AppliedMove, painting::Painting};
 
 use super::Solver;
 
@@ -10,7 +10,7 @@ impl Solver for NoOp {
         "no_op"
     }
 
-    fn solve_core(&self, _canvas: &mut Canvas, _painting: &Painting) -> Vec<AppliedMove> {
-        vec![]
+    fn solve_core(&self, _canvas: &mut Canvas, _painting: &Painting) -> Result<Vec<AppliedMove>> {
+        Ok(vec![])
     }
 }
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+
+use crate::{
+    block::BlockData,
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Move},
+    painting::Painting,
+};
+
+/// Replaces the color of every color move with the optimal color of the pixels it
+/// ends up painting. Cuts, swaps and merges don't depend on colors, so the program
+/// is replayed with every color move painting its own label instead: the rendered
+/// labels tell which move every pixel of the result comes from. The pixels of one
+/// move don't depend on the colors of the others, so each color is optimal on its
+/// own, and the costs of the moves stay the same.
+pub(super) fn optimize_colors(
+    applied_moves: &mut Vec<AppliedMove>,
+    canvas: &mut Canvas,
+    painting: &Painting,
+) -> Result<()> {
+    for am in applied_moves.iter().rev() {
+        am.clone().undo(canvas);
+    }
+
+    let mut labeled = canvas.clone();
+    let ids: Vec<_> = labeled.blocks_iter().map(|b| b.id.clone()).collect();
+    for id in ids {
+        if let Some(b) = labeled.get_block_mut(&id) {
+            match &mut b.data {
+                BlockData::Simple(c) => *c = label(0),
+                BlockData::Complex(bs) => bs.iter_mut().for_each(|b| b.c = label(0)),
+            }
+        }
+    }
+    for (i, am) in applied_moves.iter().enumerate() {
+        let mov = match &am.mov {
+            Move::Color(id, _) => Move::Color(id.clone(), label(i + 1)),
+            mov => mov.clone(),
+        };
+        mov.apply(&mut labeled)?;
+    }
+
+    let labels = labeled.render();
+    let mut counts: HashMap<usize, HashMap<Color, u32>> = HashMap::new();
+    for x in 0..painting.width() {
+        for y in 0..painting.height() {
+            let c = labels.get_color(x, y);
+            let i = u32::from_le_bytes([c.r(), c.g(), c.b(), c.a()]) as usize;
+            *counts
+                .entry(i)
+                .or_default()
+                .entry(painting.get_color(x, y))
+                .or_default() += 1;
+        }
+    }
+
+    let moves: Vec<Move> = applied_moves
+        .drain(..)
+        .enumerate()
+        .map(|(i, am)| match (am.mov, counts.get(&(i + 1))) {
+            (Move::Color(id, c), Some(counts)) => {
+                let optimal = Color::optimal(counts);
+                if distance(counts, optimal) < distance(counts, c) {
+                    Move::Color(id, optimal)
+                } else {
+                    Move::Color(id, c)
+                }
+            }
+            (mov, _) => mov,
+        })
+        .collect();
+    for mov in moves {
+        applied_moves.push(mov.apply(canvas)?);
+    }
+    Ok(())
+}
+
+/// The color standing for the move at `i` in the labeled replay, 0 being the
+/// initial canvas
+fn label(i: usize) -> Color {
+    let [r, g, b, a] = (i as u32).to_le_bytes();
+    Color::new(r, g, b, a)
+}
+
+/// The total distance of the counted colors to `c`, their similarity to it
+fn distance(counts: &HashMap<Color, u32>, c: Color) -> f64 {
+    counts
+        .iter()
+        .map(|(p, n)| {
+            let d: f64 = [
+                (p.r(), c.r()),
+                (p.g(), c.g()),
+                (p.b(), c.b()),
+                (p.a(), c.a()),
+            ]
+            .iter()
+            .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+            .sum();
+            *n as f64 * d.sqrt()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{block::Rect, moves::Orientation};
+
+    #[test]
+    fn colors_only_the_pixels_left_visible() -> Result<()> {
+        // red on the left half, blue on the right half
+        let (red, blue) = (Color::new(255, 0, 0, 255), Color::new(0, 0, 255, 255));
+        let mut data = vec![];
+        for _ in 0..20 {
+            for x in 0..20 {
+                data.push(if x < 10 { red } else { blue });
+            }
+        }
+        let painting = Painting::new(20, 20, data);
+
+        let gray = Color::new(128, 128, 128, 255);
+        let program = vec![
+            Move::Color("0".into(), gray),
+            Move::LineCut("0".into(), Orientation::Vertical, 10),
+            Move::Color("0.1".into(), gray),
+            Move::LineCut("0.1".into(), Orientation::Horizontal, 10),
+            Move::Swap("0.1.0".into(), "0.1.1".into()),
+            Move::Merge("0.1.0".into(), "0.1.1".into()),
+        ];
+        let mut canvas = Canvas::new(20, 20);
+        let mut applied_moves = vec![];
+        for mov in program {
+            applied_moves.push(mov.apply(&mut canvas)?);
+        }
+        let cost: u64 = applied_moves.iter().map(|am| am.cost.0).sum();
+        let before = painting.calculate_score_canvas(&canvas);
+
+        optimize_colors(&mut applied_moves, &mut canvas, &painting)?;
+        // the first color only shows on the left half, the second on the right one
+        assert_eq!(applied_moves[0].mov, Move::Color("0".into(), red));
+        assert_eq!(applied_moves[2].mov, Move::Color("0.1".into(), blue));
+        assert_eq!(applied_moves.iter().map(|am| am.cost.0).sum::<u64>(), cost);
+        assert!(painting.calculate_score_canvas(&canvas).0 < before.0);
+        let whole = Rect::from_coords([0, 0, 20, 20]);
+        assert_eq!(
+            canvas.render().get_pixels(&whole),
+            painting.get_pixels(&whole)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use crate::{
+    block::{Block, BlockData, BlockId},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Move, MoveType, SIMILARITY_FACTOR},
+    painting::Painting,
+};
+
+use super::Solver;
+
+/// Moves every block to its optimal position among the blocks of the same shape
+#[derive(Clone)]
+pub struct Permute;
+
+impl Solver for Permute {
+    fn name(&self) -> &'static str {
+        "permute"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let mut groups: HashMap<(u32, u32), Vec<BlockId>> = HashMap::new();
+        for b in canvas.blocks_iter() {
+            groups
+                .entry((b.r.width(), b.r.height()))
+                .or_default()
+                .push(b.id.clone());
+        }
+        let mut groups: Vec<Vec<BlockId>> = groups.into_values().filter(|g| g.len() > 1).collect();
+        for group in groups.iter_mut() {
+            group.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        groups.sort_by(|a, b| a[0].0.cmp(&b[0].0));
+
+        let mut applied_moves = vec![];
+        for ids in groups {
+            let blocks = ids
+                .iter()
+                .map(|id| canvas.get_move_block(id))
+                .collect::<std::result::Result<Vec<&Block>, _>>()?;
+            let swap_cost = canvas.compute_cost(MoveType::Swap, blocks[0].area()).0 as f64;
+            let similarity: Vec<Vec<f64>> = blocks
+                .iter()
+                .map(|content| {
+                    blocks
+                        .iter()
+                        .map(|position| {
+                            let moved = content.moved_to(position.r.bottom_left);
+                            painting.calculate_score_block(&moved) * SIMILARITY_FACTOR
+                        })
+                        .collect()
+                })
+                .collect();
+            // a swap moves two blocks, so charge half of it for every block that moves
+            let cost: Vec<Vec<f64>> = similarity
+                .iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    row.iter()
+                        .enumerate()
+                        .map(|(j, s)| if i == j { *s } else { s + swap_cost / 2.0 })
+                        .collect()
+                })
+                .collect();
+
+            let mut assignment = hungarian(&cost);
+            let colors: Vec<Option<Color>> = blocks
+                .iter()
+                .map(|b| match b.data {
+                    BlockData::Simple(c) => Some(c),
+                    BlockData::Complex(_) => None,
+                })
+                .collect();
+            split_cycles(&mut assignment, &colors);
+            // longer cycles cost more than the estimate, only keep the ones that pay off
+            for cycle in cycles(&assignment) {
+                let gain: f64 = cycle
+                    .iter()
+                    .map(|&i| similarity[i][i] - similarity[i][assignment[i]])
+                    .sum();
+                if gain <= (cycle.len() - 1) as f64 * swap_cost {
+                    continue;
+                }
+                for &next in &cycle[1..] {
+                    let mov = Move::Swap(ids[cycle[0]].clone(), ids[next].clone());
+                    applied_moves.push(mov.apply(canvas)?);
+                }
+            }
+        }
+        Ok(applied_moves)
+    }
+}
+
+/// The non-trivial cycles of the permutation where the contents at `i` go to `assignment[i]`.
+/// Each cycle `[p0, p1, ..]` is realized by swapping `p0` with `p1`, then `p2`, and so on:
+/// every swap moves the contents held by `p0` to where they belong.
+pub(super) fn cycles(assignment: &[usize]) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; assignment.len()];
+    let mut cycles = vec![];
+    for start in 0..assignment.len() {
+        if visited[start] || assignment[start] == start {
+            continue;
+        }
+        let mut cycle = vec![];
+        let mut next = start;
+        while !visited[next] {
+            visited[next] = true;
+            cycle.push(next);
+            next = assignment[next];
+        }
+        cycles.push(cycle);
+    }
+    cycles
+}
+
+/// Identical contents within a cycle are interchangeable: exchanging their targets splits
+/// the cycle in two, which saves a swap without changing the result
+fn split_cycles(assignment: &mut [usize], colors: &[Option<Color>]) {
+    'split: loop {
+        for cycle in cycles(assignment) {
+            for (k, &i) in cycle.iter().enumerate() {
+                for &j in &cycle[k + 1..] {
+                    if colors[i].is_some() && colors[i] == colors[j] {
+                        assignment.swap(i, j);
+                        continue 'split;
+                    }
+                }
+            }
+        }
+        break;
+    }
+}
+
+/// Minimum cost assignment of rows to columns of a square matrix, O(n^3)
+pub(super) fn hungarian(cost: &[Vec<f64>]) -> Vec<usize> {
+    let n = cost.len();
+    // 1-based, column 0 is a virtual one holding the row being added
+    let mut u = vec![0.0; n + 1];
+    let mut v = vec![0.0; n + 1];
+    let mut row_of = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for row in 1..=n {
+        row_of[0] = row;
+        let mut col = 0;
+        let mut min_v = vec![f64::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[col] = true;
+            let r = row_of[col];
+            let mut delta = f64::INFINITY;
+            let mut next_col = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost[r - 1][j - 1] - u[r] - v[j];
+                if reduced < min_v[j] {
+                    min_v[j] = reduced;
+                    way[j] = col;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    next_col = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            col = next_col;
+            if row_of[col] == 0 {
+                break;
+            }
+        }
+        // flip the augmenting path
+        while col != 0 {
+            let prev = way[col];
+            row_of[col] = row_of[prev];
+            col = prev;
+        }
+    }
+
+    let mut assignment = vec![0; n];
+    for j in 1..=n {
+        if row_of[j] != 0 {
+            assignment[row_of[j] - 1] = j - 1;
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hungarian_finds_optimum() {
+        let cost = vec![
+            vec![4.0, 1.0, 3.0],
+            vec![2.0, 0.0, 5.0],
+            vec![3.0, 2.0, 2.0],
+        ];
+        assert_eq!(hungarian(&cost), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn split_cycles_keeps_identical_contents_in_place() {
+        let red = Some(Color::new(255, 0, 0, 255));
+        let blue = Some(Color::new(0, 0, 255, 255));
+        // the two reds are interchangeable, only the blue block needs to move
+        let mut assignment = vec![1, 2, 0];
+        split_cycles(&mut assignment, &[red, red, blue]);
+        assert_eq!(
+            cycles(&assignment)
+                .iter()
+                .map(|c| c.len() - 1)
+                .sum::<usize>(),
+            1
+        );
+    }
+
+    #[test]
+    fn cycle_swaps_realize_permutation() {
+        let assignment = vec![2, 0, 1, 3, 5, 4];
+        // positions hold the index of their contents
+        let mut positions: Vec<usize> = (0..assignment.len()).collect();
+        let cycles = cycles(&assignment);
+        assert_eq!(cycles, vec![vec![0, 2, 1], vec![4, 5]]);
+        for cycle in &cycles {
+            for &next in &cycle[1..] {
+                positions.swap(cycle[0], next);
+            }
+        }
+        for (contents, &target) in assignment.iter().enumerate() {
+            assert_eq!(positions[target], contents);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::str::FromStr;
+
+use crate::{
+    canvas::Canvas,
+    error::{Error, Result},
+    moves::AppliedMove,
+    painting::Painting,
+};
+
+use super::{Processor, Solution, Solver};
+
+/// Parameter overrides of a solver, given as `solver@key=value,key=value`
+pub(super) struct Params {
+    values: Vec<(String, String, bool)>,
+}
+
+impl Params {
+    pub fn parse(params: &str) -> Result<Self> {
+        let mut values = vec![];
+        for param in params.split(',').filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidParameter(format!("`{param}` isn't key=value")))?;
+            values.push((key.to_owned(), value.to_owned(), false));
+        }
+        Ok(Params { values })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The value of the parameter, or the default when it isn't overridden
+    pub fn get<T: FromStr>(&mut self, key: &str, default: T) -> Result<T> {
+        Ok(self.get_optional(key)?.unwrap_or(default))
+    }
+
+    pub fn get_optional<T: FromStr>(&mut self, key: &str) -> Result<Option<T>> {
+        match self.values.iter_mut().find(|(k, ..)| k == key) {
+            Some((_, value, used)) => {
+                *used = true;
+                let parsed = value.parse().map_err(|_| {
+                    Error::InvalidParameter(format!("bad value of `{key}`: {value}"))
+                })?;
+                Ok(Some(parsed))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Fails if some of the parameters don't belong to the solver
+    pub fn finish(&self, solver_name: &str) -> Result<()> {
+        match self.values.iter().find(|(.., used)| !used) {
+            Some((key, ..)) => Err(Error::InvalidParameter(format!(
+                "`{solver_name}` has no parameter `{key}`"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A solver with overridden parameters, named after its full spec so that its
+/// solutions are kept apart from the ones of the defaults
+#[derive(Clone)]
+pub(super) struct Preset {
+    pub name: String,
+    pub solver: Box<dyn Solver>,
+}
+
+impl Solver for Preset {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        self.solver.solve_core(canvas, painting)
+    }
+
+    fn solve(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Solution> {
+        self.solver.solve(canvas, painting)
+    }
+}
+
+/// A processor with overridden parameters, named after its full spec like `Preset`
+#[derive(Clone)]
+pub(super) struct PresetProcessor {
+    pub name: String,
+    pub processor: Box<dyn Processor>,
+}
+
+impl Processor for PresetProcessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process(
+        &self,
+        applied_moves: &mut Vec<AppliedMove>,
+        canvas: &mut Canvas,
+        painting: &Painting,
+    ) -> Result<()> {
+        self.processor.process(applied_moves, canvas, painting)
+    }
+}
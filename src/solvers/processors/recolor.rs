@@ -2,6 +2,7 @@ use crate::{
     block::BlockData,
     canvas::Canvas,
     color::Color,
+    error::Result,
     moves::{AppliedMove, Move},
     painting::Painting,
     solvers::Processor,
@@ -20,7 +21,7 @@ impl Processor for Recolor {
         applied_moves: &mut Vec<AppliedMove>,
         canvas: &mut Canvas,
         painting: &Painting,
-    ) {
+    ) -> Result<()> {
         let mut new_moves = applied_moves.clone();
         for am in new_moves.iter_mut() {
             if let Move::Color(b_id, ref mut c) = &mut am.mov {
@@ -41,6 +42,7 @@ impl Processor for Recolor {
                             Color::find_most_common(&counts),
                             Color::gmedian(&colors, EPS, MAX_ITERATIONS),
                             Color::pmedian(&colors, EPS, MAX_ITERATIONS),
+                            Color::optimal(&counts),
                         ];
                         // dbg!(color_options);
                         *c = *color_options
@@ -56,7 +58,8 @@ impl Processor for Recolor {
         }
         *applied_moves = new_moves
             .into_iter()
-            .map(|am| am.mov.apply(canvas).unwrap())
-            .collect();
+            .map(|am| am.mov.apply(canvas))
+            .collect::<std::result::Result<_, _>>()?;
+        Ok(())
     }
 }
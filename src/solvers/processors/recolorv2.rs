@@ -13,6 +13,7 @@ use crate::{
     block::{BlockId, Rect},
     canvas::Canvas,
     color::Color,
+    error::Result,
     moves::{AppliedMove, Cost, Move, MoveType},
     painting::Painting,
     solvers::Processor,
@@ -25,7 +26,7 @@ fn recolor(
     canvas: &mut Canvas,
     initial_moves: &[AppliedMove],
     planned_creation_colors: HashMap<BlockId, Color>,
-) -> Vec<AppliedMove> {
+) -> Result<Vec<AppliedMove>> {
     // step 4  Rebuild the coloring history.
     // step 4.1 make a color free move history
     let color_free_moves: Vec<_> = initial_moves
@@ -44,15 +45,15 @@ fn recolor(
     // step 4.3, build the new moves by inserting coloring as color free moves are applied
     let mut new_moves: Vec<AppliedMove> = vec![];
     for free_move in color_free_moves {
-        let applied_move = free_move.mov.clone().apply(canvas).unwrap();
+        let applied_move = free_move.mov.clone().apply(canvas)?;
         new_moves.push(applied_move.clone());
         for created_block in applied_move.created_blocks() {
             if let Some(color) = planned_creation_colors.get(&created_block) {
-                new_moves.push(Move::Color(created_block, *color).apply(canvas).unwrap());
+                new_moves.push(Move::Color(created_block, *color).apply(canvas)?);
             }
         }
     }
-    new_moves
+    Ok(new_moves)
 }
 
 #[derive(Clone, Derivative)]
@@ -149,7 +150,7 @@ fn build_initial_forest(
     move_history: &Vec<AppliedMove>,
     canvas: &Canvas,
     painting: &Painting,
-) -> ColorForest {
+) -> Result<ColorForest> {
     let mut color_trees: HashMap<BlockId, Rc<ColorTree>> =
         HashMap::with_capacity(canvas.blocks_count());
 
@@ -220,19 +221,19 @@ fn build_initial_forest(
     }
 
     for mov in move_history.iter() {
-        let info = mov.mov.clone().apply(&mut canvas).unwrap();
+        let info = mov.mov.clone().apply(&mut canvas)?;
         for created_block in info.created_blocks() {
-            let block = canvas.get_block(&created_block).unwrap();
+            let block = canvas.get_move_block(&created_block)?;
             color_trees.entry(created_block).and_modify(|entry| {
                 unsafe { Rc::get_mut_unchecked(entry) }.region = Some(block.r);
             });
         }
     }
 
-    ColorForest {
+    Ok(ColorForest {
         trees: color_trees,
         roots: live_roots,
-    }
+    })
 }
 
 struct WorkCluster {
@@ -408,10 +409,11 @@ impl Processor for Recolorv2 {
         applied_moves: &mut Vec<AppliedMove>,
         canvas: &mut Canvas,
         painting: &Painting,
-    ) {
-        let mut forest = build_initial_forest(applied_moves, canvas, painting);
+    ) -> Result<()> {
+        let mut forest = build_initial_forest(applied_moves, canvas, painting)?;
         optimize_forest(&mut forest, canvas, painting);
         let planned_creation_colors = forest.get_colors();
-        *applied_moves = recolor(canvas, applied_moves, planned_creation_colors);
+        *applied_moves = recolor(canvas, applied_moves, planned_creation_colors)?;
+        Ok(())
     }
 }
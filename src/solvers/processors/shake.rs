@@ -1,12 +1,35 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::{
+    block::{BlockId, Rect},
     canvas::Canvas,
-    moves::{AppliedMove, Move, Orientation, UndoMoveOp},
+    error::Result,
+    moves::{AppliedMove, Cost, Move},
     painting::Painting,
     solvers::Processor,
 };
 
+/// Moves every cut to a better offset by coordinate descent: the offset (both
+/// coordinates of a point cut, one after the other) moves by `radius` as long as that
+/// improves the total score, then by half as much, and so on down to a single pixel.
+/// Only the moves depending on the cut are replayed, and the blocks they color that
+/// have another shape by then may switch to the optimal color of their new shape. As
+/// moves can only be undone in reverse order, the moves depending on a cut first move
+/// to the end of the program, after the ones that don't, which leaves the result
+/// unchanged.
 #[derive(Clone)]
-pub struct Shake;
+pub struct Shake {
+    pub radius: u32,
+}
+
+/// The program being shaken, along with what doesn't change while shaking it
+struct Program<'a> {
+    moves: &'a mut Vec<AppliedMove>,
+    canvas: &'a mut Canvas,
+    painting: &'a Painting,
+    /// Blocks whose contents end up somewhere else, keeping their colors
+    swapped: HashSet<BlockId>,
+}
 
 impl Processor for Shake {
     fn name(&self) -> &str {
@@ -18,154 +41,423 @@ impl Processor for Shake {
         applied_moves: &mut Vec<AppliedMove>,
         canvas: &mut Canvas,
         painting: &Painting,
-    ) {
-        let mut base_canvas = canvas.clone();
-        self.reset_canvas(&mut base_canvas, applied_moves);
+    ) -> Result<()> {
+        let mut program = Program::new(applied_moves, canvas, painting);
 
-        // THIS CODE IS VERY BAD (but it works)
         loop {
-            let mut updated = false;
-            for i in 0..applied_moves.len() {
-                let moves_copy = applied_moves.clone();
-                let am = &mut applied_moves[i];
-                let mut options = vec![];
-                let mut x_options = vec![];
-                match &am.mov {
-                    Move::LineCut(_, o, offset) => {
-                        let curr = *offset;
-                        if let UndoMoveOp::Cut { restore_blocks, .. } = &am.undo.operation {
-                            let orig_block = &restore_blocks[0];
-                            if curr < orig_block.r.x() + 3
-                                || curr >= (orig_block.r.x() + orig_block.r.width()) - 3
-                                || curr < orig_block.r.y() + 3
-                                || curr >= (orig_block.r.y() + orig_block.r.height()) - 3
-                            {
-                                continue; // Avoid overflows
-                            }
-
-                            match o {
-                                Orientation::Horizontal => {
-                                    for v in curr - 2..=(curr - 1) {
-                                        options.push(v);
-                                    }
-                                    for v in curr + 1..=(curr + 2) {
-                                        options.push(v);
-                                    }
-                                }
-                                Orientation::Vertical => {
-                                    for v in curr - 2..=(curr - 1) {
-                                        options.push(v);
-                                    }
-                                    for v in curr + 1..=(curr + 2) {
-                                        options.push(v);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Move::PointCut(_, off_x, off_y) => {
-                        if let UndoMoveOp::Cut { restore_blocks, .. } = &am.undo.operation {
-                            let orig_block = &restore_blocks[0];
-                            if *off_x < orig_block.r.x() + 3
-                                || *off_x >= (orig_block.r.x() + orig_block.r.width()) - 3
-                                || *off_y < orig_block.r.y() + 3
-                                || *off_y >= (orig_block.r.y() + orig_block.r.height()) - 3
-                            {
-                                continue; // Avoid overflows
-                            }
-
-                            for x in *off_x - 2..=(*off_x - 1) {
-                                for y in *off_y - 2..=(*off_y - 1) {
-                                    x_options.push((x, y));
-                                }
-                                for y in *off_y + 1..=(*off_y + 2) {
-                                    x_options.push((x, y));
-                                }
-                            }
-                            for x in *off_x + 1..=(*off_x + 2) {
-                                for y in *off_y - 2..=(*off_y - 1) {
-                                    x_options.push((x, y));
-                                }
-                                for y in *off_y + 1..=(*off_y + 2) {
-                                    x_options.push((x, y));
-                                }
-                            }
-                        }
-                    }
-                    _ => continue,
+            // by the block they cut, as the moves get reordered
+            let cuts: Vec<(BlockId, usize)> = program
+                .moves
+                .iter()
+                .filter_map(|am| match &am.mov {
+                    Move::LineCut(id, ..) => Some((id.clone(), 1)),
+                    Move::PointCut(id, ..) => Some((id.clone(), 2)),
+                    _ => None,
+                })
+                .collect();
+            let mut improved = false;
+            for (id, coords) in cuts {
+                let Some(i) = program.find_cut(&id) else {
+                    continue;
+                };
+                let (i, roots) = program.move_to_end(i)?;
+                for coord in 0..coords {
+                    improved |= self.descend(&mut program, i, coord, &roots)?;
+                }
+            }
+            if !improved {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Shake {
+    /// Moves one coordinate of the cut at `i` while that pays off
+    fn descend(
+        &self,
+        program: &mut Program,
+        i: usize,
+        coord: usize,
+        roots: &Option<HashSet<BlockId>>,
+    ) -> Result<bool> {
+        let mut improved = false;
+        let mut step = self.radius;
+        while step > 0 {
+            let shifted = [-(step as i64), step as i64]
+                .into_iter()
+                .filter_map(|delta| shift(&program.moves[i].mov, coord, delta))
+                .collect::<Vec<_>>();
+            let mut moved = false;
+            for mov in shifted {
+                if program.try_replace(i, mov, roots)? {
+                    (moved, improved) = (true, true);
+                    break;
+                }
+            }
+            if !moved {
+                step /= 2;
+            }
+        }
+        Ok(improved)
+    }
+}
+
+/// The cut with one of its coordinates moved by `delta`
+fn shift(mov: &Move, coord: usize, delta: i64) -> Option<Move> {
+    let moved = |v: u32| u32::try_from(v as i64 + delta).ok();
+    match (mov.clone(), coord) {
+        (Move::LineCut(id, orientation, offset), _) => {
+            Some(Move::LineCut(id, orientation, moved(offset)?))
+        }
+        (Move::PointCut(id, x, y), 0) => Some(Move::PointCut(id, moved(x)?, y)),
+        (Move::PointCut(id, x, y), _) => Some(Move::PointCut(id, x, moved(y)?)),
+        _ => None,
+    }
+}
+
+fn block_ids(mov: &Move) -> Vec<&BlockId> {
+    match mov {
+        Move::LineCut(id, ..) | Move::PointCut(id, ..) | Move::Color(id, _) => vec![id],
+        Move::Swap(a, b) | Move::Merge(a, b) => vec![a, b],
+    }
+}
+
+fn in_subtrees(id: &BlockId, roots: &HashSet<BlockId>) -> bool {
+    id.rev_parents(true).any(|p| roots.contains(&p))
+}
+
+impl<'a> Program<'a> {
+    fn new(
+        moves: &'a mut Vec<AppliedMove>,
+        canvas: &'a mut Canvas,
+        painting: &'a Painting,
+    ) -> Self {
+        let swapped = moves
+            .iter()
+            .filter_map(|am| match &am.mov {
+                Move::Swap(a, b) => Some([a.clone(), b.clone()]),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        Program {
+            moves,
+            canvas,
+            painting,
+            swapped,
+        }
+    }
+
+    fn find_cut(&self, id: &BlockId) -> Option<usize> {
+        self.moves.iter().position(|am| match &am.mov {
+            Move::LineCut(cut, ..) | Move::PointCut(cut, ..) => cut == id,
+            _ => false,
+        })
+    }
+
+    /// The moves from `i` on that depend on the move `i`, and the blocks whose
+    /// subtrees they affect. Block ids created by merges depend on the order of all
+    /// the merges, so after one of them everything depends on the move.
+    fn dependents(&self, i: usize) -> (Vec<usize>, Option<HashSet<BlockId>>) {
+        let mut roots: HashSet<BlockId> =
+            block_ids(&self.moves[i].mov).into_iter().cloned().collect();
+        let mut dependents = vec![i];
+        for j in i + 1..self.moves.len() {
+            let mov = &self.moves[j].mov;
+            if let Move::Merge(..) = mov {
+                return ((i..self.moves.len()).collect(), None);
+            }
+            let ids = block_ids(mov);
+            if ids.iter().any(|id| in_subtrees(id, &roots)) {
+                dependents.push(j);
+                if let Move::Swap(..) = mov {
+                    roots.extend(ids.into_iter().cloned());
                 }
+            }
+        }
+        (dependents, Some(roots))
+    }
+
+    /// Moves the move `i` and the ones depending on it to the end of the program,
+    /// returning its new position and the subtrees they affect
+    fn move_to_end(&mut self, i: usize) -> Result<(usize, Option<HashSet<BlockId>>)> {
+        let (dependents, roots) = self.dependents(i);
+        let start = self.moves.len() - dependents.len();
+        if dependents[0] == start {
+            return Ok((start, roots));
+        }
+        let suffix: Vec<AppliedMove> = self.moves.drain(i..).collect();
+        let (moved, kept): (Vec<_>, Vec<_>) = suffix
+            .iter()
+            .enumerate()
+            .partition(|(k, _)| dependents.contains(&(i + k)));
+        let reordered: Vec<Move> = kept
+            .into_iter()
+            .chain(moved)
+            .map(|(_, am)| am.mov.clone())
+            .collect();
+        for am in suffix.into_iter().rev() {
+            am.undo(self.canvas);
+        }
+        for mov in reordered {
+            self.moves.push(mov.apply(self.canvas)?);
+        }
+        Ok((start, roots))
+    }
+
+    /// The similarity of the blocks in the subtrees, or of the whole canvas
+    fn similarity(&self, roots: &Option<HashSet<BlockId>>) -> Cost {
+        match roots {
+            Some(roots) => Cost::from_block_cost(
+                self.canvas
+                    .blocks_iter()
+                    .filter(|b| in_subtrees(&b.id, roots))
+                    .map(|b| self.painting.calculate_score_block(b))
+                    .sum(),
+            ),
+            None => self.painting.calculate_score_canvas(self.canvas),
+        }
+    }
 
-                if !options.is_empty() {
-                    let mut best_score = painting.calculate_score_canvas(canvas);
-                    'options: for off in options {
-                        let mut new_moves = moves_copy.clone();
-                        match &mut new_moves[i].mov {
-                            Move::LineCut(_, _, ref mut offset) => {
-                                *offset = off;
-                            }
-                            _ => unreachable!(),
-                        }
-
-                        let mut attempt_canvas = base_canvas.clone();
-                        let mut new_applied_moves = vec![];
-                        for am in new_moves {
-                            match am.mov.apply(&mut attempt_canvas) {
-                                Ok(am) => new_applied_moves.push(am),
-                                Err(_) => continue 'options, // Why is this happening?
-                            }
-                        }
-                        let score = painting.calculate_score_canvas(&attempt_canvas);
-                        if score.0 < best_score.0 {
-                            // println!("Updating score: {} -> {}", best_score.0, score.0);
-                            best_score = score;
-                            *applied_moves = new_applied_moves;
-                            *canvas = attempt_canvas;
-                            updated = true;
-                        }
+    /// A color move switches to the optimal color of its block when the block has
+    /// another shape than it had when colored before, if that fits the new shape
+    /// better than its color
+    fn recolored(&self, mov: &Move, colored: Option<&Rect>) -> Move {
+        if let (Move::Color(id, c), Some(r)) = (mov, colored) {
+            if let Some(b) = self.canvas.get_block(id) {
+                if b.r != *r && !self.swapped.contains(id) {
+                    let optimal = self.painting.optimal_color(&b.r);
+                    if self.painting.calculate_score_rect(&b.r, optimal)
+                        < self.painting.calculate_score_rect(&b.r, *c)
+                    {
+                        return Move::Color(id.clone(), optimal);
                     }
-                } else if !x_options.is_empty() {
-                    let mut best_score = painting.calculate_score_canvas(canvas);
-                    'options: for (off_x, off_y) in x_options {
-                        let mut new_moves = moves_copy.clone();
-                        match &mut new_moves[i].mov {
-                            Move::PointCut(_, ref mut offset_x, ref mut offset_y) => {
-                                *offset_x = off_x;
-                                *offset_y = off_y;
-                            }
-                            _ => unreachable!(),
-                        }
-
-                        // From here to the end of this loop it's a copy-paste from the above branch :(
-                        let mut attempt_canvas = base_canvas.clone();
-                        let mut new_applied_moves = vec![];
-                        for am in new_moves {
-                            match am.mov.apply(&mut attempt_canvas) {
-                                Ok(am) => new_applied_moves.push(am),
-                                Err(_) => continue 'options, // Why is this happening?
-                            }
-                        }
-                        let score = painting.calculate_score_canvas(&attempt_canvas);
-                        if score.0 < best_score.0 {
-                            // println!("Updating score: {} -> {}", best_score.0, score.0);
-                            best_score = score;
-                            *applied_moves = new_applied_moves;
-                            *canvas = attempt_canvas;
-                            updated = true;
-                        }
+                }
+            }
+        }
+        mov.clone()
+    }
+
+    /// Replaces the move `i` and replays the moves depending on it, keeping the
+    /// result if it improves the total score
+    fn try_replace(
+        &mut self,
+        i: usize,
+        mov: Move,
+        roots: &Option<HashSet<BlockId>>,
+    ) -> Result<bool> {
+        let before = self.similarity(roots) + self.moves[i..].iter().map(|am| am.cost).sum();
+
+        let dependents: Vec<AppliedMove> = self.moves.drain(i..).collect();
+        // the shape every block had when it got colored
+        let mut colored = HashMap::new();
+        for (k, am) in dependents.iter().enumerate().rev() {
+            if let Move::Color(id, _) = &am.mov {
+                colored.extend(self.canvas.get_block(id).map(|b| (k, b.r)));
+            }
+            am.clone().undo(self.canvas);
+        }
+        let mut replayed = vec![];
+        let mut valid = true;
+        for (k, am) in dependents.iter().enumerate() {
+            let next = if k == 0 {
+                mov.clone()
+            } else {
+                self.recolored(&am.mov, colored.get(&k))
+            };
+            match next.apply(self.canvas) {
+                Ok(am) => {
+                    let empty = am
+                        .created_blocks()
+                        .iter()
+                        .any(|id| self.canvas.get_block(id).is_none_or(|b| b.area() == 0));
+                    replayed.push(am);
+                    if empty {
+                        valid = false;
+                        break;
                     }
                 }
+                Err(_) => {
+                    valid = false;
+                    break;
+                }
             }
-            if !updated {
-                break;
+        }
+        if valid {
+            let after = self.similarity(roots) + replayed.iter().map(|am| am.cost).sum();
+            if after.0 < before.0 {
+                self.moves.extend(replayed);
+                return Ok(true);
             }
         }
+
+        for am in replayed.into_iter().rev() {
+            am.undo(self.canvas);
+        }
+        for am in dependents {
+            self.moves.push(am.mov.apply(self.canvas)?);
+        }
+        Ok(false)
     }
 }
 
-impl Shake {
-    fn reset_canvas(&self, canvas: &mut Canvas, applied_moves: &[AppliedMove]) {
-        for am in applied_moves.iter().rev() {
-            am.clone().undo(canvas);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Color, moves::Orientation};
+
+    const SIZE: u32 = 40;
+
+    /// Cuts, colors, a merge of the whole canvas, and a swap of blocks of two
+    /// different cuts
+    fn make_program() -> Vec<Move> {
+        let red = Color::new(255, 0, 0, 255);
+        let blue = Color::new(0, 0, 255, 255);
+        let green = Color::new(0, 255, 0, 255);
+        vec![
+            Move::LineCut("0".into(), Orientation::Vertical, 20),
+            Move::Merge("0.0".into(), "0.1".into()),
+            Move::LineCut("1".into(), Orientation::Vertical, 10),
+            Move::LineCut("1.1".into(), Orientation::Horizontal, 20),
+            Move::Color("1.0".into(), red),
+            Move::Color("1.1.1".into(), blue),
+            Move::LineCut("1.0".into(), Orientation::Horizontal, 20),
+            Move::LineCut("1.1.0".into(), Orientation::Vertical, 20),
+            Move::Swap("1.0.0".into(), "1.1.0.0".into()),
+            Move::Color("1.0.1".into(), green),
+        ]
+    }
+
+    /// Red on the left, blue on the top right and green elsewhere, with borders the
+    /// program doesn't cut along
+    fn make_painting() -> Painting {
+        let mut data = vec![];
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                data.push(match (x, y) {
+                    (0..=13, _) => Color::new(255, 0, 0, 255),
+                    (_, 23..) => Color::new(0, 0, 255, 255),
+                    _ => Color::new(0, 255, 0, 255),
+                });
+            }
+        }
+        Painting::new(SIZE, SIZE, data)
+    }
+
+    fn apply(moves: Vec<Move>) -> Result<(Canvas, Vec<AppliedMove>)> {
+        let mut canvas = Canvas::new(SIZE, SIZE);
+        let mut applied_moves = vec![];
+        for mov in moves {
+            applied_moves.push(mov.apply(&mut canvas)?);
+        }
+        Ok((canvas, applied_moves))
+    }
+
+    fn pixels(canvas: &Canvas) -> Vec<Color> {
+        canvas
+            .render()
+            .get_pixels(&Rect::from_coords([0, 0, SIZE, SIZE]))
+    }
+
+    fn total(program: &Program) -> u64 {
+        (program.painting.calculate_score_canvas(program.canvas)
+            + program.moves.iter().map(|am| am.cost).sum())
+        .0
+    }
+
+    fn cut_ids(moves: &[AppliedMove]) -> Vec<BlockId> {
+        moves
+            .iter()
+            .filter_map(|am| match &am.mov {
+                Move::LineCut(id, ..) | Move::PointCut(id, ..) => Some(id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moving_a_cut_to_the_end_keeps_the_result() -> Result<()> {
+        let painting = make_painting();
+        let (canvas, applied_moves) = apply(make_program())?;
+        let expected = pixels(&canvas);
+        for id in cut_ids(&applied_moves) {
+            let (mut canvas, mut applied_moves) = apply(make_program())?;
+            let mut program = Program::new(&mut applied_moves, &mut canvas, &painting);
+            let i = program.find_cut(&id).unwrap();
+            let (moved, _) = program.move_to_end(i)?;
+            assert_eq!(cut_ids(&program.moves[moved..])[0], id);
+            assert_eq!(program.moves.len(), make_program().len());
+            assert_eq!(pixels(program.canvas), expected, "moving the cut of {id}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn dependents_follow_swaps_and_merges() -> Result<()> {
+        let painting = make_painting();
+        let (mut canvas, mut applied_moves) = apply(make_program())?;
+        let program = Program::new(&mut applied_moves, &mut canvas, &painting);
+        // the swap brings 1.0.0 into the subtree of 1.1, so its color follows
+        assert_eq!(program.dependents(3).0, vec![3, 5, 7, 8]);
+        assert_eq!(program.dependents(6).0, vec![6, 8, 9]);
+        // after the merge, block ids depend on the cut
+        assert_eq!(program.dependents(0), ((0..10).collect(), None));
+        Ok(())
+    }
+
+    #[test]
+    fn shifted_cuts_never_increase_the_total() -> Result<()> {
+        let painting = make_painting();
+        let (mut canvas, mut applied_moves) = apply(make_program())?;
+        let mut program = Program::new(&mut applied_moves, &mut canvas, &painting);
+        let mut previous = total(&program);
+        let mut improved = false;
+        for id in cut_ids(program.moves) {
+            let i = program.find_cut(&id).unwrap();
+            let (i, roots) = program.move_to_end(i)?;
+            for delta in -8..=8 {
+                if let Some(mov) = shift(&program.moves[i].mov, 0, delta) {
+                    improved |= program.try_replace(i, mov, &roots)?;
+                    let current = total(&program);
+                    assert!(current <= previous, "shifting the cut of {id} by {delta}");
+                    previous = current;
+                }
+            }
+        }
+        assert!(improved);
+
+        // the program still replays to the same canvas
+        let replayed = program.moves.iter().map(|am| am.mov.clone()).collect();
+        assert_eq!(pixels(&apply(replayed)?.0), pixels(program.canvas));
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_zero_area_cuts() -> Result<()> {
+        // the whole target is blue, so moving the cuts onto the border would pay off
+        let blue = Color::new(0, 0, 255, 255);
+        let painting = Painting::new(SIZE, SIZE, vec![blue; (SIZE * SIZE) as usize]);
+        for cut in [
+            Move::LineCut("0".into(), Orientation::Vertical, 20),
+            Move::PointCut("0".into(), 20, 20),
+        ] {
+            let (mut canvas, mut applied_moves) = apply(vec![
+                cut.clone(),
+                Move::Color("0.1".into(), blue),
+                Move::Color("0.0".into(), blue),
+            ])?;
+            let mut program = Program::new(&mut applied_moves, &mut canvas, &painting);
+            let expected = pixels(program.canvas);
+            let (i, roots) = program.move_to_end(0)?;
+            for coord in 0..2 {
+                if let Some(mov) = shift(&cut, coord, -20) {
+                    assert!(!program.try_replace(i, mov, &roots)?);
+                }
+            }
+            assert_eq!(program.moves[i].mov, cut);
+            assert_eq!(pixels(program.canvas), expected);
         }
+        Ok(())
     }
 }
@@ -1,4 +1,4 @@
-use crate::{solvers::Processor, moves::{AppliedMove, Move}, canvas::Canvas, painting::Painting};
+use crate::{solvers::Processor, moves::{AppliedMove, Move}, canvas::Canvas, painting::Painting, error::Result};
 
 #[derive(Clone)]
 pub struct Trim;
@@ -13,7 +13,7 @@ impl Processor for Trim {
         applied_moves: &mut Vec<AppliedMove>,
         canvas: &mut Canvas,
         _painting: &Painting,
-    ) {
+    ) -> Result<()> {
         // This code doesn't seem to properly trim :/
         if !applied_moves.is_empty() {
             for i in (0..applied_moves.len() - 1).rev() {
@@ -26,5 +26,6 @@ impl Processor for Trim {
                 }
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::{block::Rect, color::Color, moves::Orientation, painting::Painting};
+
+use super::cuts::{contrast, EdgeProfile};
+
+/// A grid of `counts` tiles of `size` starting at `origin`, all looking alike
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Repetition {
+    pub origin: (u32, u32),
+    pub size: (u32, u32),
+    pub counts: (u32, u32),
+}
+
+/// Tiles narrower than this are texture rather than a pattern worth reproducing
+const MIN_PERIOD: u32 = 8;
+/// A shift is a period when it leaves at most this mean difference per pixel
+/// (summed over the RGBA channels)...
+const MAX_PERIOD_DIFF: f64 = 32.0;
+/// ...and when that difference is at most this fraction of the typical one
+const MAX_PERIOD_RATIO: f64 = 0.1;
+/// Pixels this close to the background color (summed over the channels) are background
+const BACKGROUND_TOLERANCE: u64 = 16;
+
+impl Repetition {
+    pub fn instances(&self) -> Vec<Rect> {
+        let ((ox, oy), (w, h), (nx, ny)) = (self.origin, self.size, self.counts);
+        let mut instances = vec![];
+        for j in 0..ny {
+            for i in 0..nx {
+                let (x, y) = (ox + i * w, oy + j * h);
+                instances.push(Rect::from_coords([x, y, x + w, y + h]));
+            }
+        }
+        instances
+    }
+
+    /// The average of all the instances
+    pub fn mean_tile(&self, painting: &Painting) -> Painting {
+        let (w, h) = self.size;
+        let instances = self.instances();
+        let mut data = Vec::with_capacity((w * h) as usize);
+        for y in 0..h {
+            for x in 0..w {
+                let mut sum = [0u32; 4];
+                for r in &instances {
+                    let c = painting.get_color(r.x() + x, r.y() + y);
+                    for (s, v) in sum.iter_mut().zip([c.r(), c.g(), c.b(), c.a()]) {
+                        *s += v as u32;
+                    }
+                }
+                let [r, g, b, a] = sum.map(|s| (s / instances.len() as u32) as u8);
+                data.push(Color::new(r, g, b, a));
+            }
+        }
+        Painting::new(w, h, data)
+    }
+}
+
+/// Looks for a shift along each axis that maps the contents of the target onto
+/// themselves, and lays tiles of that size so that their borders fall on the
+/// strongest edges. The contents are whatever differs from the background, so that
+/// a frame around a pattern doesn't hide it.
+pub(super) fn find_repetition(painting: &Painting) -> Option<Repetition> {
+    let region = content_region(painting)?;
+    let (px, py) = (
+        period(painting, &region, true),
+        period(painting, &region, false),
+    );
+    if px.is_none() && py.is_none() {
+        return None;
+    }
+    let profile = EdgeProfile::new(painting);
+
+    let (w, ox, nx) = match px {
+        Some(w) => {
+            let o = origin(&profile, &region, w, true);
+            (w, region.x() + o, (region.width() - o) / w)
+        }
+        None => (region.width(), region.x(), 1),
+    };
+    let (h, oy, ny) = match py {
+        Some(h) => {
+            let o = origin(&profile, &region, h, false);
+            (h, region.y() + o, (region.height() - o) / h)
+        }
+        None => (region.height(), region.y(), 1),
+    };
+    (nx * ny >= 2).then_some(Repetition {
+        origin: (ox, oy),
+        size: (w, h),
+        counts: (nx, ny),
+    })
+}
+
+/// The bounding box of the pixels that differ from the most common border color
+fn content_region(painting: &Painting) -> Option<Rect> {
+    let (width, height) = (painting.width(), painting.height());
+    let mut border = HashMap::new();
+    for x in 0..width {
+        for y in [0, height - 1] {
+            *border.entry(painting.get_color(x, y)).or_default() += 1;
+        }
+    }
+    for y in 0..height {
+        for x in [0, width - 1] {
+            *border.entry(painting.get_color(x, y)).or_default() += 1;
+        }
+    }
+    let background = Color::find_most_common(&border);
+
+    let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+    for y in 0..height {
+        for x in 0..width {
+            if contrast(painting.get_color(x, y), background) > BACKGROUND_TOLERANCE {
+                (x0, y0) = (x0.min(x), y0.min(y));
+                (x1, y1) = (x1.max(x + 1), y1.max(y + 1));
+            }
+        }
+    }
+    (x0 < x1).then(|| Rect::from_coords([x0, y0, x1, y1]))
+}
+
+/// The mean difference between the region and itself shifted by `shift`
+fn shift_diff(painting: &Painting, region: &Rect, shift: u32, horizontal: bool) -> f64 {
+    let (mut sum, mut count) = (0u64, 0u64);
+    // every other row or column is plenty to tell periods apart
+    for y in (region.y()..region.top_right.y).step_by(if horizontal { 2 } else { 1 }) {
+        for x in (region.x()..region.top_right.x).step_by(if horizontal { 1 } else { 2 }) {
+            let (sx, sy) = if horizontal {
+                (x + shift, y)
+            } else {
+                (x, y + shift)
+            };
+            if sx < region.top_right.x && sy < region.top_right.y {
+                sum += contrast(painting.get_color(x, y), painting.get_color(sx, sy));
+                count += 1;
+            }
+        }
+    }
+    sum as f64 / count.max(1) as f64
+}
+
+/// The smallest shift along the axis that is about as good as the best one,
+/// as multiples of the period are periods too
+fn period(painting: &Painting, region: &Rect, horizontal: bool) -> Option<u32> {
+    let len = if horizontal {
+        region.width()
+    } else {
+        region.height()
+    };
+    let diffs: Vec<(u32, f64)> = (MIN_PERIOD..=len / 2)
+        .map(|p| (p, shift_diff(painting, region, p, horizontal)))
+        .collect();
+    let mut sorted: Vec<f64> = diffs.iter().map(|&(_, d)| d).collect();
+    sorted.sort_by(f64::total_cmp);
+    let median = *sorted.get(sorted.len() / 2)?;
+    let best = sorted[0];
+    if best > MAX_PERIOD_DIFF || best >= median * MAX_PERIOD_RATIO {
+        return None;
+    }
+    diffs
+        .iter()
+        .find(|&&(_, d)| d <= best * 1.1 + 1.0)
+        .map(|&(p, _)| p)
+}
+
+/// The offset of the first tile in the region, fitting as many tiles as possible,
+/// and among those the one whose tile borders cross the most contrast
+fn origin(profile: &EdgeProfile, region: &Rect, period: u32, horizontal: bool) -> u32 {
+    let (orientation, start, len) = if horizontal {
+        (Orientation::Vertical, region.x(), region.width())
+    } else {
+        (Orientation::Horizontal, region.y(), region.height())
+    };
+    (0..period)
+        .max_by_key(|&o| {
+            // the first line of the region doesn't border anything inside it
+            let strength: u64 = (o..len)
+                .step_by(period as usize)
+                .filter(|&a| a > 0)
+                .map(|a| profile.strength(region, orientation, start + a))
+                .sum();
+            ((len - o) / period, strength, std::cmp::Reverse(o))
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u32, height: u32, cell: u32) -> Painting {
+        let (black, white) = (Color::new(0, 0, 0, 255), Color::new(255, 255, 255, 255));
+        let mut data = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                data.push(if (x / cell + y / cell) & 1 == 0 {
+                    black
+                } else {
+                    white
+                });
+            }
+        }
+        Painting::new(width, height, data)
+    }
+
+    #[test]
+    fn finds_checkerboard_tiles() {
+        let rep = find_repetition(&checkerboard(100, 60, 10)).unwrap();
+        assert_eq!(
+            rep,
+            Repetition {
+                origin: (0, 0),
+                size: (20, 20),
+                counts: (5, 3),
+            }
+        );
+        let tile = rep.mean_tile(&checkerboard(100, 60, 10));
+        assert_eq!(tile.get_color(0, 0), Color::new(0, 0, 0, 255));
+        assert_eq!(tile.get_color(10, 0), Color::new(255, 255, 255, 255));
+    }
+
+    #[test]
+    fn ignores_plain_targets() {
+        let data = vec![Color::new(10, 20, 30, 255); 64 * 64];
+        assert_eq!(find_repetition(&Painting::new(64, 64, data)), None);
+    }
+}
@@ -0,0 +1,216 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    error::Result,
+    moves::AppliedMove,
+    painting::Painting,
+};
+
+use super::{dp::partition, Solver};
+
+/// Quantizes the target into a few colors with k-means, covers the resulting regions
+/// with rectangles, and lets the guillotine DP cut along the edges of the largest ones
+#[derive(Clone)]
+pub struct Segment {
+    pub colors: usize,
+    /// How many cut coordinates per axis the DP gets, besides the block borders
+    pub max_coords: usize,
+}
+
+const KMEANS_ITERATIONS: usize = 10;
+/// Rectangles smaller than this are noise and don't contribute cut coordinates
+const MIN_RECT_AREA: u32 = 64;
+/// Coordinates closer than this are considered the same edge
+const MIN_GAP: u32 = 4;
+
+impl Solver for Segment {
+    fn name(&self) -> &'static str {
+        "segment"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let labels = quantize(painting, self.colors);
+        let rects = rectangles(&labels, painting.width(), painting.height());
+
+        // every coordinate is weighted by the length of the rectangle edges lying on it
+        let mut x_weights: HashMap<u32, u64> = HashMap::new();
+        let mut y_weights: HashMap<u32, u64> = HashMap::new();
+        for r in rects.iter().filter(|r| r.area() >= MIN_RECT_AREA) {
+            for x in [r.x(), r.top_right.x] {
+                *x_weights.entry(x).or_default() += r.height() as u64;
+            }
+            for y in [r.y(), r.top_right.y] {
+                *y_weights.entry(y).or_default() += r.width() as u64;
+            }
+        }
+
+        let mut roots: Vec<(BlockId, Rect)> =
+            canvas.blocks_iter().map(|b| (b.id.clone(), b.r)).collect();
+        roots.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+
+        let mut applied_moves = vec![];
+        for (id, r) in roots {
+            let xs = pick_coords(&x_weights, r.x(), r.top_right.x, self.max_coords);
+            let ys = pick_coords(&y_weights, r.y(), r.top_right.y, self.max_coords);
+            partition(canvas, painting, &sums, &id, xs, ys, &mut applied_moves)?;
+        }
+        Ok(applied_moves)
+    }
+}
+
+/// The nearest k-means color of every pixel, indexed by `x + y * width`
+fn quantize(painting: &Painting, k: usize) -> Vec<usize> {
+    let (w, h) = (painting.width(), painting.height());
+    let mut pixels = Vec::with_capacity((w * h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let c = painting.get_color(x, y);
+            pixels.push([c.r(), c.g(), c.b(), c.a()].map(|v| v as f64));
+        }
+    }
+    let distance = |a: &[f64; 4], b: &[f64; 4]| -> f64 {
+        a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+    };
+    let nearest = |centers: &[[f64; 4]], p: &[f64; 4]| -> (usize, f64) {
+        centers
+            .iter()
+            .map(|c| distance(c, p))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap()
+    };
+
+    // farthest point initialization, starting from the average color
+    let mean = |members: &mut dyn Iterator<Item = &[f64; 4]>| {
+        let mut sum = [0.0; 4];
+        let mut count = 0.0;
+        for p in members {
+            for (s, v) in sum.iter_mut().zip(p) {
+                *s += v;
+            }
+            count += 1.0;
+        }
+        (count > 0.0).then(|| sum.map(|s| s / count))
+    };
+    let mut centers = vec![mean(&mut pixels.iter()).unwrap_or_default()];
+    while centers.len() < k.max(1) {
+        let (farthest, d) = pixels
+            .iter()
+            .map(|p| nearest(&centers, p).1)
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap_or_default();
+        if d == 0.0 {
+            break;
+        }
+        centers.push(pixels[farthest]);
+    }
+
+    let mut labels = vec![0; pixels.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        for (label, p) in labels.iter_mut().zip(&pixels) {
+            *label = nearest(&centers, p).0;
+        }
+        for (i, center) in centers.iter_mut().enumerate() {
+            let mut members = pixels
+                .iter()
+                .zip(&labels)
+                .filter(|(_, &l)| l == i)
+                .map(|(p, _)| p);
+            if let Some(m) = mean(&mut members) {
+                *center = m;
+            }
+        }
+    }
+    labels
+}
+
+/// Covers the label map with rectangles of a single label each, growing every one
+/// to the right and then upwards from its bottom left pixel
+fn rectangles(labels: &[usize], width: u32, height: u32) -> Vec<Rect> {
+    let idx = |x: u32, y: u32| (x + y * width) as usize;
+    let mut covered = vec![false; labels.len()];
+    let mut rects = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            if covered[idx(x, y)] {
+                continue;
+            }
+            let label = labels[idx(x, y)];
+            let free = |covered: &[bool], x: u32, y: u32| {
+                !covered[idx(x, y)] && labels[idx(x, y)] == label
+            };
+            let mut x1 = x + 1;
+            while x1 < width && free(&covered, x1, y) {
+                x1 += 1;
+            }
+            let mut y1 = y + 1;
+            while y1 < height && (x..x1).all(|xx| free(&covered, xx, y1)) {
+                y1 += 1;
+            }
+            for yy in y..y1 {
+                for xx in x..x1 {
+                    covered[idx(xx, yy)] = true;
+                }
+            }
+            rects.push(Rect::from_coords([x, y, x1, y1]));
+        }
+    }
+    rects
+}
+
+/// The block borders along with the heaviest coordinates strictly inside them, sorted
+pub(super) fn pick_coords(
+    weights: &HashMap<u32, u64>,
+    from: u32,
+    to: u32,
+    max_coords: usize,
+) -> Vec<u32> {
+    let mut candidates: Vec<(u32, u64)> = weights
+        .iter()
+        .filter(|(&c, _)| from < c && c < to)
+        .map(|(&c, &w)| (c, w))
+        .collect();
+    candidates.sort_by_key(|&(c, w)| (Reverse(w), c));
+
+    let mut coords = vec![from, to];
+    for (c, _) in candidates {
+        if coords.len() >= max_coords + 2 {
+            break;
+        }
+        if coords.iter().all(|&k| k.abs_diff(c) >= MIN_GAP) {
+            coords.push(c);
+        }
+    }
+    coords.sort();
+    coords
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectangles_cover_the_regions() {
+        // an L shaped region of label 1 around a square of label 0
+        #[rustfmt::skip]
+        let labels = vec![
+            1, 1, 1, 1,
+            0, 0, 1, 1,
+            0, 0, 1, 1,
+        ];
+        let rects = rectangles(&labels, 4, 3);
+        assert_eq!(
+            rects,
+            vec![
+                Rect::from_coords([0, 0, 4, 1]),
+                Rect::from_coords([0, 1, 2, 3]),
+                Rect::from_coords([2, 1, 4, 3]),
+            ]
+        );
+        assert_eq!(rects.iter().map(|r| r.area()).sum::<u32>(), 12);
+    }
+}
@@ -3,21 +3,33 @@ use std::collections::HashMap;
 use crate::{
     block::{Block, BlockId},
     canvas::Canvas,
-    moves::{AppliedMove, Cost, Move, MoveType, Orientation, UndoMoveOp},
+    error::Result,
+    moves::{AppliedMove, Cost, Move, MoveType, UndoMoveOp},
     painting::Painting,
 };
 
-use super::Solver;
+use super::{
+    cuts::{Cut, CutSource, EdgeProfile},
+    Solver,
+};
 
 #[derive(Clone)]
 pub struct Simple {
     pub allow_cross_cut: bool,
-    pub step_1: bool,
+    /// Distance between the candidate line cuts
+    pub step: u32,
+    /// Distance between the candidate point cuts
+    pub xstep: u32,
+    /// Try the cuts along this many of the strongest edges of every block instead
+    /// of the ones every `step`, when not zero
+    pub edges: usize,
 }
 
 impl Solver for Simple {
     fn name(&self) -> &'static str {
-        if !self.step_1 {
+        if self.edges > 0 {
+            "simple_edges"
+        } else if self.step != 1 {
             if self.allow_cross_cut {
                 "simple"
             } else {
@@ -30,8 +42,13 @@ impl Solver for Simple {
         }
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove> {
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
         let mut applied_moves = vec![];
+        let profile = (self.edges > 0).then(|| EdgeProfile::new(painting));
+        let source = match &profile {
+            Some(profile) => CutSource::Edges(profile, self.edges),
+            None => CutSource::Grid(self.step, self.xstep),
+        };
 
         let mut total_move_cost = Cost(0);
         let mut current_painting_score = painting.calculate_score_canvas(canvas);
@@ -45,7 +62,8 @@ impl Solver for Simple {
                 let mov = match best_moves_cache.get(&b.id) {
                     Some(v) => v.clone(),
                     None => {
-                        let mov = self.get_best_move_for_block(b, canvas, painting, budget);
+                        let mov =
+                            self.get_best_move_for_block(b, canvas, painting, source, budget)?;
                         best_moves_cache.insert(b.id.to_owned(), mov.clone());
                         mov
                     }
@@ -60,7 +78,7 @@ impl Solver for Simple {
             }
 
             let mov = best_moves.into_iter().min_by_key(|(_, r)| *r).unwrap().0;
-            let am = mov.apply(canvas).unwrap();
+            let am = mov.apply(canvas)?;
             total_move_cost += am.cost;
             let undo_op = am.undo.operation.clone();
             applied_moves.push(am);
@@ -70,11 +88,11 @@ impl Solver for Simple {
             } = undo_op
             {
                 for b_id in delete_block_ids {
-                    let b = canvas.get_block(&b_id).unwrap();
+                    let b = canvas.get_move_block(&b_id)?;
                     let before = painting.calculate_score_canvas(canvas);
                     let color = painting.calculate_average_color(&b.r);
                     let mov = Move::Color(b_id.to_owned(), color);
-                    let am = mov.apply(canvas).unwrap();
+                    let am = mov.apply(canvas)?;
                     let after = painting.calculate_score_canvas(canvas);
                     if (after.0 + am.cost.0) > before.0 {
                         am.undo(canvas);
@@ -88,7 +106,7 @@ impl Solver for Simple {
             current_painting_score = painting.calculate_score_canvas(canvas);
         }
 
-        applied_moves
+        Ok(applied_moves)
     }
 }
 
@@ -98,71 +116,54 @@ impl Simple {
         b: &Block,
         canvas: &Canvas,
         painting: &Painting,
+        source: CutSource,
         budget: i64,
-    ) -> Option<(Move, i64)> {
-        let (step, xstep) = if self.step_1 { (1, 1) } else { (2, 20) };
+    ) -> Result<Option<(Move, i64)>> {
+        let r = &b.r;
+        let cuts = source.cuts(r);
+        let linear_cut_cost = canvas.compute_cost(MoveType::LineCut, r.area());
+        let cross_cut_cost = canvas.compute_cost(MoveType::PointCut, r.area());
 
         let mut best_move = None;
         let mut best_result = i64::MAX;
-
-        let r = &b.r;
-        let linear_cut_cost = canvas.compute_cost(MoveType::LineCut, r.area());
-        if (linear_cut_cost.0 as i64) < budget {
-            for x in (step..r.width()).step_by(step as usize) {
-                let mov = Move::LineCut(b.id.clone(), Orientation::Vertical, r.x() + x);
-                let result = self.assess_move(&mov, canvas, painting);
-                if result < best_result {
-                    best_result = result;
-                    best_move = Some(mov);
-                }
-            }
-            for y in (step..r.height()).step_by(step as usize) {
-                let mov = Move::LineCut(b.id.clone(), Orientation::Horizontal, r.y() + y);
-                let result = self.assess_move(&mov, canvas, painting);
-                if result < best_result {
-                    best_result = result;
-                    best_move = Some(mov);
-                }
+        for cut in cuts {
+            let allowed = match cut {
+                Cut::Line(..) => (linear_cut_cost.0 as i64) < budget,
+                Cut::Point(..) => self.allow_cross_cut && (cross_cut_cost.0 as i64) < budget,
+            };
+            if !allowed {
+                continue;
             }
-        }
-        if self.allow_cross_cut {
-            let cross_cut_cost = canvas.compute_cost(MoveType::PointCut, r.area());
-            if (cross_cut_cost.0 as i64) < budget {
-                for x in (xstep..r.width() - 1).step_by(xstep as usize) {
-                    for y in (xstep..r.height() - 1).step_by(xstep as usize) {
-                        let mov = Move::PointCut(b.id.clone(), r.x() + x, r.y() + y);
-                        let result = self.assess_move(&mov, canvas, painting);
-                        if result < best_result {
-                            best_result = result;
-                            best_move = Some(mov);
-                        }
-                    }
-                }
+            let mov = cut.to_move(b.id.clone());
+            let result = self.assess_move(&mov, canvas, painting)?;
+            if result < best_result {
+                best_result = result;
+                best_move = Some(mov);
             }
         }
 
         if best_result > 0 {
-            None
+            Ok(None)
         } else {
-            best_move.map(|m| (m, best_result))
+            Ok(best_move.map(|m| (m, best_result)))
         }
     }
 
     // Lower result is better
-    fn assess_move(&self, mov: &Move, canvas: &Canvas, painting: &Painting) -> i64 {
+    fn assess_move(&self, mov: &Move, canvas: &Canvas, painting: &Painting) -> Result<i64> {
         let mut canvas_temp = canvas.clone();
-        let am = mov.clone().apply(&mut canvas_temp).unwrap();
+        let am = mov.clone().apply(&mut canvas_temp)?;
         let mut total_move_cost = am.cost;
         if let UndoMoveOp::Cut {
             delete_block_ids, ..
         } = &am.undo.operation
         {
             for b_id in delete_block_ids {
-                let b = canvas_temp.get_block(b_id).unwrap();
+                let b = canvas_temp.get_move_block(b_id)?;
                 let before = painting.calculate_score_canvas(&canvas_temp);
                 let color = painting.calculate_average_color(&b.r);
                 let mov = Move::Color(b_id.to_owned(), color);
-                let am = mov.apply(&mut canvas_temp).unwrap();
+                let am = mov.apply(&mut canvas_temp)?;
                 let after = painting.calculate_score_canvas(&canvas_temp);
                 if (after.0 + am.cost.0) > before.0 {
                     am.undo(&mut canvas_temp);
@@ -174,6 +175,6 @@ impl Simple {
         let before = painting.calculate_score_canvas(canvas);
         let after = painting.calculate_score_canvas(&canvas_temp);
         let improvement = after.0 as i64 - before.0 as i64;
-        total_move_cost.0 as i64 + improvement
+        Ok(total_move_cost.0 as i64 + improvement)
     }
 }
@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    block::{Block, BlockData, BlockId, Rect},
+    canvas::Canvas,
+    error::Result,
+    moves::{AppliedMove, Cost, Move, MoveType, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::{
+    merge_plan::merge_region,
+    permute::{cycles, hungarian},
+    Solver,
+};
+
+/// Cuts the blocks carrying source image content into a grid, then decides for every
+/// cell whether to keep its content, swap in the content of another cell, or repaint it
+#[derive(Clone)]
+pub struct SourceReuse {
+    /// The grid has 2^depth cells per side, every depth up to this one is tried
+    pub max_depth: u32,
+}
+
+impl Solver for SourceReuse {
+    fn name(&self) -> &'static str {
+        "source_reuse"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let mut roots: Vec<BlockId> = canvas
+            .blocks_iter()
+            .filter(|b| matches!(b.data, BlockData::Complex(_)))
+            .map(|b| b.id.clone())
+            .collect();
+        roots.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut applied_moves = vec![];
+        for root in roots {
+            let mut best: Option<(u64, Canvas, Vec<AppliedMove>)> = None;
+            for depth in 0..=self.max_depth {
+                let mut candidate = canvas.clone();
+                let moves = reuse_block(&mut candidate, &root, depth, painting, &sums)?;
+                let cost = moves.iter().map(|am| am.cost).sum::<Cost>();
+                let total = (painting.calculate_score_canvas(&candidate) + cost).0;
+                if best
+                    .as_ref()
+                    .is_none_or(|(best_total, ..)| total < *best_total)
+                {
+                    best = Some((total, candidate, moves));
+                }
+            }
+            if let Some((_, best_canvas, moves)) = best {
+                *canvas = best_canvas;
+                applied_moves.extend(moves);
+            }
+        }
+        Ok(applied_moves)
+    }
+}
+
+fn reuse_block(
+    canvas: &mut Canvas,
+    root: &BlockId,
+    depth: u32,
+    painting: &Painting,
+    sums: &ColorSums,
+) -> Result<Vec<AppliedMove>> {
+    let mut applied_moves = vec![];
+    let mut cells = vec![];
+    split(canvas, root.clone(), depth, &mut cells, &mut applied_moves)?;
+
+    // only cells of the same shape can be swapped
+    let mut groups: Vec<Vec<BlockId>> = vec![];
+    let mut group_of: HashMap<(u32, u32), usize> = HashMap::new();
+    for id in cells {
+        let r = canvas.get_move_block(&id)?.r;
+        let idx = *group_of.entry((r.width(), r.height())).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[idx].push(id);
+    }
+
+    let mut repaint = vec![];
+    for ids in groups {
+        repaint.extend(assign(canvas, &ids, painting, sums, &mut applied_moves)?);
+    }
+    repaint_cells(canvas, &repaint, painting, sums, &mut applied_moves)?;
+    Ok(applied_moves)
+}
+
+/// Splits the block into a 2^depth by 2^depth grid with point cuts
+fn split(
+    canvas: &mut Canvas,
+    id: BlockId,
+    depth: u32,
+    cells: &mut Vec<BlockId>,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<()> {
+    let r = canvas.get_move_block(&id)?.r;
+    if depth == 0 || r.width() < 2 || r.height() < 2 {
+        cells.push(id);
+        return Ok(());
+    }
+    let am = Move::PointCut(id, r.x() + r.width() / 2, r.y() + r.height() / 2).apply(canvas)?;
+    let children = am.created_blocks();
+    applied_moves.push(am);
+    for child in children {
+        split(canvas, child, depth - 1, cells, applied_moves)?;
+    }
+    Ok(())
+}
+
+/// The cost of coloring the block with its average color, including the resulting score
+fn repaint_cost(canvas: &Canvas, painting: &Painting, sums: &ColorSums, r: &Rect) -> f64 {
+    let color_cost = canvas.compute_cost(MoveType::Color, r.area()).0 as f64;
+    painting.calculate_score_rect(r, sums.average(r)) * SIMILARITY_FACTOR + color_cost
+}
+
+/// Moves the contents of the same-shaped cells where they fit best, and returns
+/// the cells that are better off repainted
+fn assign(
+    canvas: &mut Canvas,
+    ids: &[BlockId],
+    painting: &Painting,
+    sums: &ColorSums,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<Vec<BlockId>> {
+    let n = ids.len();
+    let blocks = ids
+        .iter()
+        .map(|id| canvas.get_move_block(id))
+        .collect::<std::result::Result<Vec<&Block>, _>>()?;
+    let swap_cost = canvas.compute_cost(MoveType::Swap, blocks[0].area()).0 as f64;
+
+    // rows are the cell contents followed by repaint tokens, columns are the cells
+    // followed by slots for the contents and tokens that end up unused
+    let mut cost = vec![vec![0.0; 2 * n]; 2 * n];
+    for (i, position) in blocks.iter().enumerate() {
+        for (j, content) in blocks.iter().enumerate() {
+            // a swap moves two blocks, so charge half of it for every block that moves
+            let moving = if i == j { 0.0 } else { swap_cost / 2.0 };
+            let moved = content.moved_to(position.r.bottom_left);
+            cost[j][i] = painting.calculate_score_block(&moved) * SIMILARITY_FACTOR + moving;
+        }
+        let repaint = repaint_cost(canvas, painting, sums, &position.r);
+        for row in cost[n..].iter_mut() {
+            row[i] = repaint;
+        }
+    }
+    let matching = hungarian(&cost);
+
+    let mut target: Vec<Option<usize>> = matching[..n]
+        .iter()
+        .map(|&i| (i < n).then_some(i))
+        .collect();
+    let mut filled = vec![false; n];
+    for &i in target.iter().flatten() {
+        filled[i] = true;
+    }
+    let repaint: Vec<usize> = (0..n).filter(|&i| !filled[i]).collect();
+    // the unused contents end up in the repainted cells. Following the contents pushed
+    // out from a repainted cell always leads to an unused one, sending that one back
+    // closes the chain into a cycle without extra swaps.
+    for &r in &repaint {
+        let mut cur = r;
+        while let Some(next) = target[cur] {
+            cur = next;
+        }
+        target[cur] = Some(r);
+    }
+    let assignment: Vec<usize> = target.into_iter().map(|t| t.unwrap()).collect();
+
+    for cycle in cycles(&assignment) {
+        for &next in &cycle[1..] {
+            let mov = Move::Swap(ids[cycle[0]].clone(), ids[next].clone());
+            applied_moves.push(mov.apply(canvas)?);
+        }
+    }
+    Ok(repaint.into_iter().map(|i| ids[i].clone()).collect())
+}
+
+/// Colors the cells with their average colors. Neighbouring cells are grouped into
+/// rectangles, which get merged first when a single color move pays for the merges.
+fn repaint_cells(
+    canvas: &mut Canvas,
+    cells: &[BlockId],
+    painting: &Painting,
+    sums: &ColorSums,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<()> {
+    let mut by_corner = HashMap::new();
+    for id in cells {
+        let r = canvas.get_move_block(id)?.r;
+        by_corner.insert((r.x(), r.y()), (id.clone(), r));
+    }
+    let mut corners: Vec<(u32, u32)> = by_corner.keys().copied().collect();
+    corners.sort_by_key(|&(x, y)| (y, x));
+
+    let mut used = HashSet::new();
+    for corner in corners {
+        if used.contains(&corner) {
+            continue;
+        }
+        let (_, first) = by_corner[&corner];
+        let free = |x: u32, y: u32, height: u32| {
+            !used.contains(&(x, y))
+                && by_corner
+                    .get(&(x, y))
+                    .is_some_and(|(_, r)| r.height() == height)
+        };
+
+        // extend to the right along the row, then up as long as the whole row fits
+        let mut row = vec![(first.x(), first.y())];
+        let mut region = first;
+        while free(region.top_right.x, region.y(), first.height()) {
+            let (_, r) = by_corner[&(region.top_right.x, region.y())];
+            row.push((r.x(), r.y()));
+            region.top_right.x = r.top_right.x;
+        }
+        let mut group = row.clone();
+        loop {
+            let y = region.top_right.y;
+            let Some((_, above)) = by_corner.get(&(region.x(), y)) else {
+                break;
+            };
+            let height = above.height();
+            let mut next_row = vec![];
+            let mut x = region.x();
+            while x < region.top_right.x && free(x, y, height) {
+                next_row.push((x, y));
+                x = by_corner[&(x, y)].1.top_right.x;
+            }
+            if x != region.top_right.x {
+                break;
+            }
+            region.top_right.y += height;
+            group.extend(next_row);
+        }
+        used.extend(group.iter().copied());
+
+        let separate: f64 = group
+            .iter()
+            .map(|c| repaint_cost(canvas, painting, sums, &by_corner[c].1))
+            .sum();
+        if group.len() > 1 {
+            let merges = merge_region(canvas, &region)?;
+            let merge_cost = merges.iter().map(|am| am.cost.0).sum::<u64>() as f64;
+            if merge_cost + repaint_cost(canvas, painting, sums, &region) < separate {
+                let merged_id = merges.last().unwrap().created_blocks().remove(0);
+                applied_moves.extend(merges);
+                let mov = Move::Color(merged_id, sums.average(&region));
+                applied_moves.push(mov.apply(canvas)?);
+                continue;
+            }
+            for am in merges.into_iter().rev() {
+                am.undo(canvas);
+            }
+        }
+        for c in &group {
+            let (id, r) = &by_corner[c];
+            applied_moves.push(Move::Color(id.clone(), sums.average(r)).apply(canvas)?);
+        }
+    }
+    Ok(())
+}
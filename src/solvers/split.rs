@@ -0,0 +1,69 @@
+use crate::{
+    block::{BlockId, Rect},
+    canvas::Canvas,
+    error::Result,
+    moves::AppliedMove,
+};
+
+use super::cuts::{Cut, CutSource, EdgeProfile};
+
+const LINE_CANDIDATES: u32 = 32;
+const POINT_CANDIDATES: u32 = 16;
+
+/// The strongest `edges` of the target when asked for, otherwise line cuts at
+/// `LINE_CANDIDATES` evenly spread offsets and point cuts on a `POINT_CANDIDATES`
+/// wide grid
+pub(super) fn split_source(profile: &EdgeProfile, edges: usize) -> CutSource<'_> {
+    if edges > 0 {
+        CutSource::Edges(profile, edges)
+    } else {
+        CutSource::Spread(LINE_CANDIDATES, POINT_CANDIDATES)
+    }
+}
+
+/// The cut of `r` from the source with the lowest estimated cost: the cut itself
+/// plus the estimate of every child
+pub(super) fn best_split(
+    canvas: &Canvas,
+    r: &Rect,
+    source: CutSource,
+    child_estimate: impl Fn(&Rect) -> f64,
+) -> Option<Cut> {
+    source
+        .cuts(r)
+        .into_iter()
+        .filter_map(|split| {
+            let children = split.child_rects(r)?;
+            let estimate = canvas.compute_cost(split.move_type(), r.area()).0 as f64
+                + children.iter().map(&child_estimate).sum::<f64>();
+            Some((estimate, split))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, split)| split)
+}
+
+/// Cuts the block and solves every child with `solve_child`, returning the cost of
+/// the cut plus the totals of the children
+pub(super) fn split_block(
+    canvas: &mut Canvas,
+    id: &BlockId,
+    split: Cut,
+    moves: &mut Vec<AppliedMove>,
+    mut solve_child: impl FnMut(&mut Canvas, &BlockId, &mut Vec<AppliedMove>) -> Result<f64>,
+) -> Result<f64> {
+    let am = split.to_move(id.clone()).apply(canvas)?;
+    let mut total = am.cost.0 as f64;
+    let children = am.created_blocks();
+    moves.push(am);
+    for child in children {
+        total += solve_child(canvas, &child, moves)?;
+    }
+    Ok(total)
+}
+
+/// Undoes the moves made after the first `mark` ones, when a split didn't pay off
+pub(super) fn undo_since(canvas: &mut Canvas, moves: &mut Vec<AppliedMove>, mark: usize) {
+    while moves.len() > mark {
+        moves.pop().unwrap().undo(canvas);
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    block::{BlockData, BlockId, Rect},
+    canvas::Canvas,
+    color::Color,
+    error::Result,
+    moves::{AppliedMove, Move, MoveType, Orientation, SIMILARITY_FACTOR},
+    painting::{ColorSums, Painting},
+};
+
+use super::{cuts::EdgeProfile, Solver};
+
+/// Cuts every initial block into horizontal bands, then every band into segments,
+/// choosing both sets of boundaries with a 1D DP over candidates `step` pixels apart
+#[derive(Clone)]
+pub struct Strips {
+    pub step: u32,
+    /// When not zero, the candidates are this many of the strongest edges of the
+    /// block per axis instead
+    pub edges: usize,
+}
+
+impl Solver for Strips {
+    fn name(&self) -> &'static str {
+        "strips"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = (self.edges > 0).then(|| EdgeProfile::new(painting));
+        let mut roots: Vec<(BlockId, Rect, Option<Color>)> = canvas
+            .blocks_iter()
+            .map(|b| {
+                let background = match b.data {
+                    BlockData::Simple(c) => Some(c),
+                    BlockData::Complex(_) => None,
+                };
+                (b.id.clone(), b.r, background)
+            })
+            .collect();
+        roots.sort_by(|a, b| a.0 .0.cmp(&b.0 .0));
+
+        let mut applied_moves = vec![];
+        for (id, r, background) in roots {
+            let (xs, ys) = match &profile {
+                Some(profile) => (
+                    profile.coords(&r, Orientation::Vertical, self.edges),
+                    profile.coords(&r, Orientation::Horizontal, self.edges),
+                ),
+                None => (
+                    coords(r.x(), r.top_right.x, self.step),
+                    coords(r.y(), r.top_right.y, self.step),
+                ),
+            };
+            let plan = Plan {
+                canvas,
+                painting,
+                sums: &sums,
+                background,
+                xs,
+                ys,
+            };
+            let bands = plan.pieces();
+            emit(canvas, &bands, id, &mut applied_moves)?;
+        }
+        Ok(applied_moves)
+    }
+}
+
+fn coords(from: u32, to: u32, step: u32) -> Vec<u32> {
+    let mut coords: Vec<u32> = (from..to).step_by(step.max(1) as usize).collect();
+    coords.push(to);
+    coords
+}
+
+/// The best choice for the rest of a block, starting at some candidate index
+#[derive(Clone, Copy)]
+struct Step {
+    cost: f64,
+    /// Where the next cut goes, or the end of the block when nothing is cut
+    next: usize,
+}
+
+struct Plan<'a> {
+    canvas: &'a Canvas,
+    painting: &'a Painting,
+    sums: &'a ColorSums,
+    background: Option<Color>,
+    xs: Vec<u32>,
+    ys: Vec<u32>,
+}
+
+impl<'a> Plan<'a> {
+    fn move_cost(&self, mov: MoveType, area: u32) -> f64 {
+        self.canvas.compute_cost(mov, area).0 as f64
+    }
+
+    /// The estimated cost of keeping the block or coloring it with its average color,
+    /// whichever is cheaper. The similarity is estimated as `sqrt(n * squared distance)`.
+    fn leaf(&self, r: &Rect) -> f64 {
+        let n = r.area() as f64;
+        let colored = self.move_cost(MoveType::Color, r.area())
+            + (n * self.sums.squared_error(r)).sqrt() * SIMILARITY_FACTOR;
+        match self.background {
+            Some(bg) => {
+                colored.min((n * self.sums.squared_distance(r, bg)).sqrt() * SIMILARITY_FACTOR)
+            }
+            None => colored,
+        }
+    }
+
+    /// The color of a piece of the final plan, if any, comparing the exact scores of
+    /// keeping the background and of coloring the piece with its average color
+    fn color(&self, r: &Rect) -> Option<Color> {
+        let average = self.sums.average(r);
+        let colored = self.move_cost(MoveType::Color, r.area())
+            + self.painting.calculate_score_rect(r, average) * SIMILARITY_FACTOR;
+        match self.background {
+            Some(bg)
+                if self.painting.calculate_score_rect(r, bg) * SIMILARITY_FACTOR <= colored =>
+            {
+                None
+            }
+            _ => Some(average),
+        }
+    }
+
+    /// 1D DP over cut positions: `segment(i, k)` is the cost of the piece between the
+    /// candidates `i` and `k`, and cutting off a piece costs a line cut on everything
+    /// that is left, as the remainder keeps getting cut
+    fn chain(
+        n: usize,
+        mut segment: impl FnMut(usize, usize) -> f64,
+        remainder_cut: impl Fn(usize) -> f64,
+    ) -> Vec<Step> {
+        let last = n - 1;
+        let mut best = vec![
+            Step {
+                cost: 0.0,
+                next: last
+            };
+            n
+        ];
+        for i in (0..last).rev() {
+            let mut step = Step {
+                cost: segment(i, last),
+                next: last,
+            };
+            let cut = remainder_cut(i);
+            for (k, rest) in best.iter().enumerate().take(last).skip(i + 1) {
+                if cut >= step.cost {
+                    break;
+                }
+                let cost = cut + rest.cost + segment(i, k);
+                if cost < step.cost {
+                    step = Step { cost, next: k };
+                }
+            }
+            best[i] = step;
+        }
+        best
+    }
+
+    /// The vertical splits of the band between the candidates `y0` and `y1`
+    fn segments(&self, y0: usize, y1: usize) -> Vec<Step> {
+        let (bottom, top) = (self.ys[y0], self.ys[y1]);
+        let right = *self.xs.last().unwrap();
+        let rect = |i: usize, k: usize| Rect::from_coords([self.xs[i], bottom, self.xs[k], top]);
+        Self::chain(
+            self.xs.len(),
+            |i, k| self.leaf(&rect(i, k)),
+            |i| self.move_cost(MoveType::LineCut, (right - self.xs[i]) * (top - bottom)),
+        )
+    }
+
+    /// The bands, bottom to top, each with its segments, left to right
+    fn pieces(&self) -> Vec<Vec<Piece>> {
+        let n = self.ys.len();
+        let top = *self.ys.last().unwrap();
+        let width = self.xs.last().unwrap() - self.xs[0];
+        // the splits of every band, indexed by its bottom then its top
+        let mut splits: Vec<Vec<Option<Vec<Step>>>> = vec![vec![None; n]; n];
+        let bands = Self::chain(
+            n,
+            |i, k| {
+                let steps = self.segments(i, k);
+                let cost = steps[0].cost;
+                splits[i][k] = Some(steps);
+                cost
+            },
+            |i| self.move_cost(MoveType::LineCut, width * (top - self.ys[i])),
+        );
+
+        let mut result = vec![];
+        let mut i = 0;
+        while i < n - 1 {
+            let k = bands[i].next;
+            let steps = splits[i][k].take().unwrap();
+            let mut pieces = vec![];
+            let mut j = 0;
+            while j < self.xs.len() - 1 {
+                let l = steps[j].next;
+                let rect = Rect::from_coords([self.xs[j], self.ys[i], self.xs[l], self.ys[k]]);
+                pieces.push(Piece {
+                    color: self.color(&rect),
+                    rect,
+                });
+                j = l;
+            }
+            result.push(pieces);
+            i = k;
+        }
+        result
+    }
+}
+
+/// A segment of a band, and its color if it gets one
+struct Piece {
+    rect: Rect,
+    color: Option<Color>,
+}
+
+/// Cuts the bands off the bottom of the block, and the segments off the left of every band
+fn emit(
+    canvas: &mut Canvas,
+    bands: &[Vec<Piece>],
+    id: BlockId,
+    applied_moves: &mut Vec<AppliedMove>,
+) -> Result<()> {
+    let mut rest = id;
+    for (b, pieces) in bands.iter().enumerate() {
+        let mut band = rest.clone();
+        if b + 1 < bands.len() {
+            let y = pieces[0].rect.top_right.y;
+            let mov = Move::LineCut(rest.clone(), Orientation::Horizontal, y);
+            applied_moves.push(mov.apply(canvas)?);
+            band = rest.new_child("0");
+            rest = rest.new_child("1");
+        }
+        for (s, piece) in pieces.iter().enumerate() {
+            let mut segment = band.clone();
+            if s + 1 < pieces.len() {
+                let x = piece.rect.top_right.x;
+                let mov = Move::LineCut(band.clone(), Orientation::Vertical, x);
+                applied_moves.push(mov.apply(canvas)?);
+                segment = band.new_child("0");
+                band = band.new_child("1");
+            }
+            if let Some(c) = piece.color {
+                applied_moves.push(Move::Color(segment, c).apply(canvas)?);
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::{
     block::BlockData,
     canvas::Canvas,
+    error::Result,
     moves::{AppliedMove, Move},
     painting::Painting,
 };
@@ -15,13 +16,16 @@ impl Solver for Swapper {
         "swapper"
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove> {
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
         let mut applied_moves = vec![];
         loop {
             let mut best_painting_score = painting.calculate_score_canvas(canvas);
             let mut best_move = None;
-            let b0_id = painting.find_worst_block_id(canvas);
-            let b0 = canvas.get_block(b0_id).unwrap();
+            let b0_id = match painting.find_worst_block_id(canvas) {
+                Some(b0_id) => b0_id,
+                None => break,
+            };
+            let b0 = canvas.get_move_block(b0_id)?;
             for b1 in canvas.blocks_iter() {
                 if b0.id == b1.id {
                     continue;
@@ -33,7 +37,7 @@ impl Solver for Swapper {
 
                     let mov = Move::Swap(b0.id.to_owned(), b1.id.to_owned());
                     let mut canvas_temp = canvas.clone();
-                    let am = mov.clone().apply(&mut canvas_temp).unwrap();
+                    let am = mov.clone().apply(&mut canvas_temp)?;
                     let score = painting.calculate_score_canvas(&canvas_temp);
                     if (score.0 + am.cost.0) < best_painting_score.0 {
                         best_painting_score = score;
@@ -42,12 +46,12 @@ impl Solver for Swapper {
                 }
             }
             if let Some(mov) = best_move {
-                let am = mov.apply(canvas).unwrap();
+                let am = mov.apply(canvas)?;
                 applied_moves.push(am);
             } else {
                 break;
             }
         }
-        applied_moves
+        Ok(applied_moves)
     }
 }
@@ -0,0 +1,115 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{canvas::Canvas, error::Result, moves::AppliedMove, painting::Painting};
+
+use super::{
+    annealing::{accept, Annealing, Neighborhood, State, T_END_RATIO},
+    cuts::{CutSource, EdgeProfile},
+    seeded_rng, Solver,
+};
+
+/// Parallel tempering: annealing chains at fixed, geometrically spaced
+/// temperatures run on all cores and exchange states between neighbours
+#[derive(Clone)]
+pub struct Tempering {
+    pub chains: usize,
+    /// Per chain
+    pub iterations: u32,
+    /// How many iterations the chains run between state exchanges
+    pub swap_interval: u32,
+    pub step: u32,
+    /// Cut along the strongest edges rather than the grid, see `Annealing::edges`
+    pub edges: usize,
+    pub seed: Option<u64>,
+}
+
+struct Replica {
+    state: State,
+    best: State,
+    temperature: f64,
+    rng: StdRng,
+}
+
+impl Replica {
+    fn run(&mut self, neighborhood: &Neighborhood, iterations: u32) {
+        for _ in 0..iterations {
+            if let Some(candidate) = neighborhood.neighbor(&self.state, &mut self.rng) {
+                if accept(
+                    self.state.total,
+                    candidate.total,
+                    self.temperature,
+                    &mut self.rng,
+                ) {
+                    self.state = candidate;
+                    if self.state.total < self.best.total {
+                        self.best = self.state.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Solver for Tempering {
+    fn name(&self) -> &'static str {
+        "tempering"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let sums = painting.color_sums();
+        let profile = EdgeProfile::new(painting);
+        let neighborhood = Neighborhood {
+            initial: canvas.clone(),
+            painting,
+            sums: &sums,
+            source: CutSource::new(&profile, self.step, self.edges),
+        };
+        let start = neighborhood.initial_state();
+        let t0 = Annealing::initial_temperature(&start);
+        let mut rng = seeded_rng(self.seed);
+
+        let chains = self.chains.max(1);
+        let mut replicas: Vec<Replica> = (0..chains)
+            .map(|i| {
+                let ratio = if chains > 1 {
+                    i as f64 / (chains - 1) as f64
+                } else {
+                    1.0
+                };
+                Replica {
+                    state: start.clone(),
+                    best: start.clone(),
+                    temperature: t0 * T_END_RATIO.powf(ratio),
+                    rng: StdRng::seed_from_u64(rng.gen()),
+                }
+            })
+            .collect();
+
+        let swap_interval = self.swap_interval.max(1);
+        let rounds = (self.iterations / swap_interval).max(1);
+        for round in 0..rounds {
+            replicas
+                .par_iter_mut()
+                .for_each(|r| r.run(&neighborhood, swap_interval));
+
+            // alternate between even and odd pairs so that every pair gets a chance
+            for i in ((round as usize % 2)..chains.saturating_sub(1)).step_by(2) {
+                let (a, b) = (&replicas[i], &replicas[i + 1]);
+                let delta = (1.0 / a.temperature - 1.0 / b.temperature)
+                    * (a.state.total as f64 - b.state.total as f64);
+                if delta >= 0.0 || rng.gen::<f64>() < delta.exp() {
+                    let (left, right) = replicas.split_at_mut(i + 1);
+                    std::mem::swap(&mut left[i].state, &mut right[0].state);
+                }
+            }
+        }
+
+        let best = replicas
+            .into_iter()
+            .map(|r| r.best)
+            .min_by_key(|s| s.total)
+            .unwrap_or(start);
+        neighborhood.apply_to(best.moves, canvas)
+    }
+}
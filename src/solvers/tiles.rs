@@ -0,0 +1,126 @@
+use std::{cmp::Reverse, collections::HashMap};
+
+use crate::{
+    block::Rect,
+    canvas::Canvas,
+    color::Color,
+    error::{Error, Result},
+    moves::{AppliedMove, Move, MoveType, Orientation, SIMILARITY_FACTOR},
+    painting::Painting,
+};
+
+use super::{
+    cuts::EdgeProfile, dp::partition, merge_plan::merge_region, repetition::find_repetition,
+    segment::pick_coords, Solver,
+};
+
+/// Paints targets made of a repeated tile. Swaps move block contents around but
+/// can't copy them, so every instance still gets painted, and painting a tile
+/// somewhere else before swapping it in costs the same as in place, as move costs
+/// only depend on the block size, plus the swap. The repetition pays off by
+/// painting the color shared by all the instances in one move, and by restricting a
+/// guillotine partition of the whole target to the edges of the average tile repeated
+/// at every instance, so that a single cut along an edge shared by a row of instances
+/// serves all of them at once.
+#[derive(Clone)]
+pub struct Tiles {
+    /// How many cut coordinates per axis every instance gets, besides its borders
+    pub max_edges: usize,
+}
+
+/// Cut coordinates per axis of the whole partition, which takes `O(n^5)` time
+const MAX_COORDS: usize = 24;
+/// How far an edge of the average tile moves to match the target
+const SNAP: u32 = 3;
+
+impl Solver for Tiles {
+    fn name(&self) -> &'static str {
+        "tiles"
+    }
+
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
+        let rep = find_repetition(painting)
+            .ok_or_else(|| Error::Solver("tiles: the target has no repeated tiles".to_owned()))?;
+        let sums = painting.color_sums();
+        let tile = rep.mean_tile(painting);
+        let ((ox, oy), (w, h), (nx, ny)) = (rep.origin, rep.size, rep.counts);
+        let (tile_profile, target_profile) = (EdgeProfile::new(&tile), EdgeProfile::new(painting));
+        let tile_rect = Rect::from_coords([0, 0, w, h]);
+        let whole = Rect::from_coords([0, 0, painting.width(), painting.height()]);
+        let tile_x = tile_profile.lines(&tile_rect, Orientation::Vertical);
+        let tile_y = tile_profile.lines(&tile_rect, Orientation::Horizontal);
+        let target_x = target_profile.lines(&whole, Orientation::Vertical);
+        let target_y = target_profile.lines(&whole, Orientation::Horizontal);
+        let xs = self.coords(&tile_x, &target_x, (ox, w, nx), painting.width());
+        let ys = self.coords(&tile_y, &target_y, (oy, h, ny), painting.height());
+
+        let mut applied_moves = vec![];
+        if canvas.blocks_iter().count() > 1 {
+            applied_moves.extend(merge_region(canvas, &whole)?);
+        }
+        let root = canvas.blocks_iter().next().unwrap().id.clone();
+
+        // the layer shared by all the instances, kept wherever it fits
+        let background = Color::find_most_common(&painting.count_colors(&whole));
+        let kept = painting.calculate_score_block(canvas.get_move_block(&root)?);
+        let colored = painting.calculate_score_rect(&whole, background) * SIMILARITY_FACTOR
+            + canvas.compute_cost(MoveType::Color, whole.area()).0 as f64;
+        if colored < kept * SIMILARITY_FACTOR {
+            applied_moves.push(Move::Color(root.clone(), background).apply(canvas)?);
+        }
+
+        partition(canvas, painting, &sums, &root, xs, ys, &mut applied_moves)?;
+        Ok(applied_moves)
+    }
+}
+
+impl Tiles {
+    /// The target borders, and the strongest edges of the tile at every instance.
+    /// The tile size is a whole number of pixels while the period of the target
+    /// might not be, so every edge moves to the strongest target edge close to it.
+    fn coords(
+        &self,
+        tile_edges: &HashMap<u32, u64>,
+        target_edges: &HashMap<u32, u64>,
+        (origin, size, count): (u32, u32, u32),
+        len: u32,
+    ) -> Vec<u32> {
+        let per_tile = (MAX_COORDS / count as usize).saturating_sub(1);
+        let tile_coords = pick_coords(tile_edges, 0, size, self.max_edges.min(per_tile));
+        let strength = |c: u32| target_edges.get(&c).copied().unwrap_or_default();
+        let snap = |c: u32| {
+            (c.saturating_sub(SNAP)..=c + SNAP)
+                .filter(|k| 0 < *k && *k < len)
+                .max_by_key(|&k| (strength(k), Reverse(k.abs_diff(c))))
+                .unwrap_or(c)
+        };
+        // including the partial instances around the whole ones
+        let mut coords = vec![];
+        let first = -((origin / size) as i64) - 1;
+        for i in first..=count as i64 {
+            let start = origin as i64 + i * size as i64;
+            let instance = tile_coords.iter().map(|&c| start + c as i64);
+            coords.extend(
+                instance
+                    .filter(|&c| 0 < c && c < len as i64)
+                    .map(|c| snap(c as u32)),
+            );
+        }
+        coords.sort();
+        // coordinates closer than the snapping distance are the same edge
+        let mut merged = vec![0];
+        for c in coords {
+            let last = merged.last_mut().unwrap();
+            if c - *last > SNAP {
+                merged.push(c);
+            } else if *last != 0 && strength(c) > strength(*last) {
+                *last = c;
+            }
+        }
+        if merged.len() > 1 && len - merged.last().unwrap() <= SNAP {
+            merged.pop();
+        }
+        merged.push(len);
+        merged
+    }
+}
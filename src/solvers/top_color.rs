@@ -1,7 +1,8 @@
 use crate::{
     canvas::Canvas,
     color::Color,
-    moves::{AppliedMove, Move, MoveError},
+    error::Result,
+    moves::{AppliedMove, Move},
     painting::Painting,
 };
 
@@ -12,8 +13,11 @@ pub struct TopColor {
     pub use_avg: bool,
 }
 
-fn apply_batch(moves: Vec<Move>, canvas: &mut Canvas) -> Result<Vec<AppliedMove>, MoveError> {
-    moves.into_iter().map(|mov| mov.apply(canvas)).collect()
+fn apply_batch(moves: Vec<Move>, canvas: &mut Canvas) -> Result<Vec<AppliedMove>> {
+    moves
+        .into_iter()
+        .map(|mov| Ok(mov.apply(canvas)?))
+        .collect()
 }
 
 impl Solver for TopColor {
@@ -21,7 +25,7 @@ impl Solver for TopColor {
         "top_color"
     }
 
-    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Vec<AppliedMove> {
+    fn solve_core(&self, canvas: &mut Canvas, painting: &Painting) -> Result<Vec<AppliedMove>> {
         let mut moves = Vec::with_capacity(canvas.blocks_count());
 
         for block in canvas.blocks_iter() {
@@ -37,6 +41,6 @@ impl Solver for TopColor {
             moves.push(mov);
         }
 
-        apply_batch(moves, canvas).expect("TopColor solver: couldn't perform color move")
+        apply_batch(moves, canvas)
     }
 }
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use icfpc2022::{create_solver, dto::SolvedSolutionDto, parse_moves_from_file, Cost, Problem};
+
+fn load_problem(id: u8) -> Problem {
+    Problem::load(&PathBuf::from(format!("./problems/{id}.png"))).unwrap()
+}
+
+#[test]
+fn best_solution_reproduces_recorded_score() {
+    let problem = load_problem(1);
+    let moves = parse_moves_from_file("./solutions/best/1.txt").unwrap();
+    let meta: SolvedSolutionDto =
+        serde_json::from_str(&std::fs::read_to_string("./solutions/best/1_meta.json").unwrap())
+            .unwrap();
+
+    let mut canvas = problem.initial_canvas.clone();
+    let mut cost = Cost(0);
+    for mov in moves {
+        cost += mov.apply(&mut canvas).unwrap().cost;
+    }
+    let score = problem.reference_painting.calculate_score_canvas(&canvas);
+
+    assert_eq!(cost.0, meta.solution_cost);
+    assert_eq!(score.0, meta.score);
+}
+
+#[test]
+fn named_solver_runs_on_problem() {
+    let problem = load_problem(1);
+    let solver = create_solver(None, "top_color").unwrap();
+    let mut canvas = problem.initial_canvas.clone();
+    let solution = solver.solve(&mut canvas, &problem.reference_painting).unwrap();
+
+    assert_eq!(solution.moves.len(), 1);
+    let score = problem.reference_painting.calculate_score(&solution.result);
+    let initial_score = problem
+        .reference_painting
+        .calculate_score_canvas(&problem.initial_canvas);
+    assert!(score.0 < initial_score.0);
+}